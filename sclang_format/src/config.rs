@@ -0,0 +1,474 @@
+// src/config.rs
+//
+// Project configuration: `sclang-format.toml`, discovered by walking up from
+// the file being formatted (or the CWD for stdin).
+
+use crate::engine::{DEFAULT_MAX_PASSES, DEFAULT_MAX_WIDTH, IndentStyle};
+use crate::error::Error;
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// File name searched for in every ancestor directory.
+pub const CONFIG_FILE_NAME: &str = "sclang-format.toml";
+
+/// Names of the [`Phase`]s, as written in configs and on the command line.
+pub const PHASES: &[&str] = &["pre", "inline", "post", "all"];
+
+/// Which part of the formatter runs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    /// Normalize raw bytes: line endings, BOM, form feeds, mixed indentation.
+    Pre,
+    /// The layout pipeline (or the Doc printer, see [`Engine`]).
+    Inline,
+    /// Final cleanup and the check that the result still parses.
+    Post,
+    /// `Pre`, `Inline` and `Post`, in that order.
+    #[default]
+    All,
+}
+
+impl Phase {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Pre => "pre",
+            Self::Inline => "inline",
+            Self::Post => "post",
+            Self::All => "all",
+        }
+    }
+
+    /// True if running `self` includes running `part`.
+    #[must_use]
+    pub fn includes(self, part: Self) -> bool {
+        self == part || self == Self::All
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+impl FromStr for Phase {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "pre" => Ok(Self::Pre),
+            "inline" => Ok(Self::Inline),
+            "post" => Ok(Self::Post),
+            "all" => Ok(Self::All),
+            _ => bail!(
+                "unknown phase `{s}` (expected one of {})",
+                PHASES.join(", ")
+            ),
+        }
+    }
+}
+
+/// Which rules of the inline pipeline run, keyed by `Rule::name()`.
+///
+/// With `only` set, exactly those rules run. Otherwise every rule that is on
+/// by default runs, plus `enable`, minus `disable`. The pipeline order is
+/// fixed either way.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuleSelection {
+    pub enable: Vec<String>,
+    pub disable: Vec<String>,
+    pub only: Vec<String>,
+}
+
+impl RuleSelection {
+    #[must_use]
+    pub fn is_enabled(&self, name: &str, default: bool) -> bool {
+        let listed = |list: &[String]| list.iter().any(|n| n == name);
+        if !self.only.is_empty() {
+            return listed(&self.only);
+        }
+        if listed(&self.disable) {
+            false
+        } else {
+            default || listed(&self.enable)
+        }
+    }
+
+    /// Switch `name` on, overriding an earlier `disable`.
+    pub fn enable(&mut self, name: &str) {
+        self.disable.retain(|n| n != name);
+        if !self.enable.iter().any(|n| n == name) {
+            self.enable.push(name.to_string());
+        }
+    }
+
+    /// Switch `name` off, overriding an earlier `enable`.
+    pub fn disable(&mut self, name: &str) {
+        self.enable.retain(|n| n != name);
+        if !self.disable.iter().any(|n| n == name) {
+            self.disable.push(name.to_string());
+        }
+    }
+
+    /// Every rule name mentioned anywhere in the selection.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.enable
+            .iter()
+            .chain(&self.disable)
+            .chain(&self.only)
+            .map(String::as_str)
+    }
+}
+
+/// How the inline phase lays the code out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Engine {
+    /// The rule pipeline, each rule patching the text in place.
+    #[default]
+    Rules,
+    /// Print the whole file from its syntax tree through the Doc IR
+    /// (`printer`). Makes no semantic rewrites.
+    Doc,
+}
+
+/// Where each line's indentation comes from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndentStrategy {
+    /// Keep the indentation as written, only converting it to the
+    /// configured style.
+    #[default]
+    Preserve,
+    /// Derive it from nesting depth (`indent_by_ast_level`), so pasted,
+    /// mis-indented code comes out indented like the rest.
+    Ast,
+}
+
+/// Part of the input to format; edits outside it are discarded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FormatRange {
+    /// 1-based line numbers, both ends inclusive.
+    Lines { start: usize, end: usize },
+    /// Byte offsets, end exclusive.
+    Bytes { start: usize, end: usize },
+}
+
+impl FormatRange {
+    /// Reject a range that ends before it starts, or lines counted from 0.
+    fn check(self) -> Result<(), String> {
+        let (start, end) = match self {
+            Self::Lines { start: 0, .. } => return Err("lines are numbered from 1".to_string()),
+            Self::Lines { start, end } | Self::Bytes { start, end } => (start, end),
+        };
+        if start > end {
+            return Err(format!("range start {start} is after end {end}"));
+        }
+        Ok(())
+    }
+
+    /// Resolve to byte offsets into `src`, clamped to its length. A line
+    /// range covers its last line including the newline.
+    #[must_use]
+    pub fn to_bytes(self, src: &str) -> Range<usize> {
+        match self {
+            Self::Bytes { start, end } => start.min(src.len())..end.min(src.len()),
+            Self::Lines { start, end } => {
+                let line_start = |line: usize| {
+                    if line <= 1 {
+                        return 0;
+                    }
+                    src.match_indices('\n')
+                        .nth(line - 2)
+                        .map_or(src.len(), |(i, _)| i + 1)
+                };
+                line_start(start)..line_start(end.saturating_add(1))
+            }
+        }
+    }
+}
+
+/// Everything that influences the formatter's output.
+///
+/// Deserializes directly from `sclang-format.toml`; missing keys take the
+/// defaults below, so an empty file is a valid config.
+///
+/// ```toml
+/// max_width = 100
+/// phase = "all"
+/// engine = "rules"
+/// indent_strategy = "ast"
+///
+/// [indent]
+/// style = "tabs"
+///
+/// [rules]
+/// disable = ["arg_to_pipe_params"]
+/// enable = ["indent_by_ast_level"]
+/// ```
+// Each flag is an independent switch in the config file.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FormatOptions {
+    pub max_width: usize,
+    /// Passes over the inline pipeline that may change the buffer; one more
+    /// checks that it settled.
+    pub max_passes: usize,
+    /// Fail with [`crate::Error::NonConvergence`] when the inline pipeline
+    /// has not settled after `max_passes`, instead of warning and keeping
+    /// the last pass.
+    pub strict_convergence: bool,
+    pub phase: Phase,
+    pub engine: Engine,
+    #[serde(rename = "indent")]
+    pub indent_style: IndentStyle,
+    pub indent_strategy: IndentStrategy,
+    pub rules: RuleSelection,
+    /// Format input that has syntax errors instead of refusing to.
+    pub allow_errors: bool,
+    /// Format input that has syntax errors, but leave the top-level
+    /// statements containing them untouched. Takes precedence over
+    /// `allow_errors`.
+    pub skip_errors: bool,
+    /// Check after every rule that the token stream is unchanged, apart from
+    /// the rewrites semantic rules are meant to make.
+    pub verify: bool,
+    /// Only format part of the input. Never read from a config file.
+    #[serde(skip)]
+    pub range: Option<FormatRange>,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            max_width: DEFAULT_MAX_WIDTH,
+            max_passes: DEFAULT_MAX_PASSES,
+            strict_convergence: false,
+            phase: Phase::default(),
+            engine: Engine::default(),
+            indent_style: IndentStyle::Spaces { width: 4 },
+            indent_strategy: IndentStrategy::default(),
+            rules: RuleSelection::default(),
+            allow_errors: false,
+            skip_errors: false,
+            verify: false,
+            range: None,
+        }
+    }
+}
+
+impl FormatOptions {
+    /// Parse options from the contents of a config file.
+    ///
+    /// # Errors
+    /// Fails on malformed TOML, unknown keys, an unknown phase, or values
+    /// [`Self::check`] rejects.
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let opts: Self = toml::from_str(text)?;
+        opts.check()?;
+        Ok(opts)
+    }
+
+    /// Reject options no run could honour.
+    ///
+    /// # Errors
+    /// Fails with [`Error::InvalidOptions`] if a rule name is unknown, if
+    /// `max_passes` is 0 (every change would count as not settling), or if
+    /// the range ends before it starts or counts lines from 0.
+    pub fn check(&self) -> Result<(), Error> {
+        crate::rules::select(&self.rules).map_err(|e| Error::InvalidOptions(e.to_string()))?;
+        if self.max_passes == 0 {
+            return Err(Error::InvalidOptions(
+                "max_passes must be at least 1".to_string(),
+            ));
+        }
+        if let Some(range) = self.range {
+            range.check().map_err(Error::InvalidOptions)?;
+        }
+        Ok(())
+    }
+
+    /// Start from the defaults and change only what is needed:
+    ///
+    /// ```
+    /// use sclang_format::{FormatOptions, IndentStyle, Phase};
+    ///
+    /// let opts = FormatOptions::builder()
+    ///     .phase(Phase::Inline)
+    ///     .indent_style(IndentStyle::Tabs)
+    ///     .max_width(100)
+    ///     .disable("arg_to_pipe_params")
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(opts.max_width, 100);
+    /// ```
+    pub fn builder() -> FormatOptionsBuilder {
+        FormatOptionsBuilder::default()
+    }
+
+    /// The inline rules to run: `rules`, plus `indent_by_ast_level` under
+    /// the `ast` indent strategy, even when `only` lists other rules.
+    #[must_use]
+    pub fn inline_rules(&self) -> RuleSelection {
+        const RULE: &str = "indent_by_ast_level";
+        let mut rules = self.rules.clone();
+        if self.indent_strategy == IndentStrategy::Ast {
+            rules.enable(RULE);
+            if !rules.only.is_empty() && !rules.only.iter().any(|n| n == RULE) {
+                rules.only.push(RULE.to_string());
+            }
+        }
+        rules
+    }
+
+    /// Render the options back as TOML (used by `--print-config`).
+    ///
+    /// # Errors
+    /// Only fails if serialization itself fails.
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+}
+
+/// Builds a [`FormatOptions`]; see [`FormatOptions::builder`].
+#[derive(Clone, Debug, Default)]
+#[must_use]
+pub struct FormatOptionsBuilder {
+    opts: FormatOptions,
+}
+
+impl FormatOptionsBuilder {
+    pub const fn phase(mut self, phase: Phase) -> Self {
+        self.opts.phase = phase;
+        self
+    }
+
+    pub const fn engine(mut self, engine: Engine) -> Self {
+        self.opts.engine = engine;
+        self
+    }
+
+    pub const fn indent_style(mut self, style: IndentStyle) -> Self {
+        self.opts.indent_style = style;
+        self
+    }
+
+    pub const fn indent_strategy(mut self, strategy: IndentStrategy) -> Self {
+        self.opts.indent_strategy = strategy;
+        self
+    }
+
+    pub const fn max_width(mut self, columns: usize) -> Self {
+        self.opts.max_width = columns;
+        self
+    }
+
+    pub const fn max_passes(mut self, passes: usize) -> Self {
+        self.opts.max_passes = passes;
+        self
+    }
+
+    pub const fn strict_convergence(mut self, strict: bool) -> Self {
+        self.opts.strict_convergence = strict;
+        self
+    }
+
+    /// Switch on a rule by `Rule::name()`, including one that is off by
+    /// default.
+    pub fn enable(mut self, rule: &str) -> Self {
+        self.opts.rules.enable(rule);
+        self
+    }
+
+    pub fn disable(mut self, rule: &str) -> Self {
+        self.opts.rules.disable(rule);
+        self
+    }
+
+    /// Run exactly these rules and no others.
+    pub fn only<I, S>(mut self, rules: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.opts.rules.only = rules.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn rules(mut self, rules: RuleSelection) -> Self {
+        self.opts.rules = rules;
+        self
+    }
+
+    pub const fn allow_errors(mut self, yes: bool) -> Self {
+        self.opts.allow_errors = yes;
+        self
+    }
+
+    pub const fn skip_errors(mut self, yes: bool) -> Self {
+        self.opts.skip_errors = yes;
+        self
+    }
+
+    pub const fn verify(mut self, yes: bool) -> Self {
+        self.opts.verify = yes;
+        self
+    }
+
+    pub const fn range(mut self, range: Option<FormatRange>) -> Self {
+        self.opts.range = range;
+        self
+    }
+
+    /// # Errors
+    /// Fails with [`Error::InvalidOptions`] if [`FormatOptions::check`]
+    /// rejects the options.
+    pub fn build(self) -> Result<FormatOptions, Error> {
+        self.opts.check()?;
+        Ok(self.opts)
+    }
+}
+
+/// Walk up from `start` (a file or directory) looking for `sclang-format.toml`.
+#[must_use]
+pub fn find_config(start: &Path) -> Option<PathBuf> {
+    let start = std::path::absolute(start).unwrap_or_else(|_| start.to_path_buf());
+    let dir = if start.is_file() {
+        start.parent()?.to_path_buf()
+    } else {
+        start
+    };
+    dir.ancestors()
+        .map(|d| d.join(CONFIG_FILE_NAME))
+        .find(|p| p.is_file())
+}
+
+/// Load a config file.
+///
+/// # Errors
+/// Fails if the file cannot be read or does not parse.
+pub fn load(path: &Path) -> Result<FormatOptions> {
+    let text = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    FormatOptions::from_toml(&text).with_context(|| format!("parsing {}", path.display()))
+}
+
+/// Find and load the config governing `start`, falling back to defaults.
+/// Also returns the path the options were read from, if any.
+///
+/// # Errors
+/// Fails if a config file was found but could not be loaded.
+pub fn discover(start: &Path) -> Result<(FormatOptions, Option<PathBuf>)> {
+    match find_config(start) {
+        Some(path) => Ok((load(&path)?, Some(path))),
+        None => Ok((FormatOptions::default(), None)),
+    }
+}
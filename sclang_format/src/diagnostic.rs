@@ -0,0 +1,231 @@
+// src/diagnostic.rs
+//
+// Syntax errors found in the input: where tree-sitter inserted ERROR or
+// MISSING nodes, and how to show them to a user. Also the warnings that do
+// not stop formatting.
+
+use crate::directives::UnknownRule;
+use crate::rules::NonConvergence;
+use std::fmt::{self, Write};
+use std::ops::Range;
+use tree_sitter::{Node, Tree};
+
+/// One ERROR or MISSING node of the parse tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyntaxError {
+    /// Byte range in the input; empty for MISSING nodes.
+    pub bytes: Range<usize>,
+    /// 1-based line of the start.
+    pub line: usize,
+    /// 1-based column of the start, in characters.
+    pub column: usize,
+    pub message: String,
+    /// The whole line the error starts on, without its newline.
+    pub source_line: String,
+}
+
+impl SyntaxError {
+    fn new(node: Node<'_>, src: &str) -> Self {
+        let bytes = node.byte_range();
+        let line_start = src[..bytes.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = src[bytes.start..]
+            .find('\n')
+            .map_or(src.len(), |i| bytes.start + i);
+        let message = if node.is_missing() {
+            format!("missing `{}`", node.kind())
+        } else {
+            "syntax error".to_string()
+        };
+        Self {
+            line: node.start_position().row + 1,
+            column: src[line_start..bytes.start].chars().count() + 1,
+            message,
+            source_line: src[line_start..line_end].trim_end_matches('\r').to_string(),
+            bytes,
+        }
+    }
+
+    /// `name:line:col: message`, followed by the offending line with the
+    /// error underlined.
+    #[must_use]
+    pub fn render(&self, name: &str) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "{name}:{self}");
+
+        let gutter = self.line.to_string();
+        let pad = " ".repeat(gutter.len());
+        // Keep tabs in the lead-in so the carets line up under them.
+        let lead: String = self
+            .source_line
+            .chars()
+            .take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        // One caret per character of the error, up to the end of the line.
+        let mut covered = 0;
+        let width = self
+            .source_line
+            .chars()
+            .skip(self.column - 1)
+            .take_while(|c| {
+                let inside = covered < self.bytes.len();
+                covered += c.len_utf8();
+                inside
+            })
+            .count()
+            .max(1);
+        let _ = writeln!(out, "{pad} |");
+        let _ = writeln!(out, "{gutter} | {}", self.source_line);
+        let _ = writeln!(out, "{pad} | {lead}{}", "^".repeat(width));
+        out
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// ERROR and MISSING nodes of `tree`, in source order. Nodes inside an
+/// ERROR node are not listed separately.
+fn error_nodes(tree: &Tree) -> Vec<Node<'_>> {
+    fn walk<'t>(node: Node<'t>, out: &mut Vec<Node<'t>>) {
+        if node.is_error() || node.is_missing() {
+            out.push(node);
+            return;
+        }
+        if !node.has_error() {
+            return;
+        }
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            walk(child, out);
+        }
+    }
+    let mut out = Vec::new();
+    walk(tree.root_node(), &mut out);
+    out
+}
+
+/// Every syntax error in `tree`, in source order.
+#[must_use]
+pub fn syntax_errors(tree: &Tree, src: &str) -> Vec<SyntaxError> {
+    error_nodes(tree)
+        .into_iter()
+        .map(|n| SyntaxError::new(n, src))
+        .collect()
+}
+
+/// A top-level statement left unformatted because it contains syntax errors.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SkippedRegion {
+    pub bytes: Range<usize>,
+    /// 1-based, inclusive.
+    pub lines: (usize, usize),
+    pub errors: Vec<SyntaxError>,
+}
+
+impl fmt::Display for SkippedRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.lines {
+            (first, last) if first == last => write!(f, "line {first}")?,
+            (first, last) => write!(f, "lines {first}-{last}")?,
+        }
+        if let Some(err) = self.errors.first() {
+            write!(f, " ({err})")?;
+        }
+        Ok(())
+    }
+}
+
+/// The smallest top-level statements of `tree` that contain all of its
+/// syntax errors, in source order. If the parser could not make out any
+/// statement, this is the whole input.
+#[must_use]
+pub fn error_regions(tree: &Tree, src: &str) -> Vec<SkippedRegion> {
+    let root = tree.root_node();
+    let mut out: Vec<SkippedRegion> = Vec::new();
+    for node in error_nodes(tree) {
+        let mut top = node;
+        while let Some(parent) = top.parent()
+            && parent != root
+        {
+            top = parent;
+        }
+        let err = SyntaxError::new(node, src);
+        match out.last_mut() {
+            Some(last) if top.start_byte() < last.bytes.end => last.errors.push(err),
+            _ => out.push(SkippedRegion {
+                bytes: top.byte_range(),
+                lines: (top.start_position().row + 1, top.end_position().row + 1),
+                errors: vec![err],
+            }),
+        }
+    }
+    out
+}
+
+/// Returned by the formatter when the input does not parse and errors are
+/// not allowed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyntaxErrors(pub Vec<SyntaxError>);
+
+impl fmt::Display for SyntaxErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.as_slice() {
+            [one] => write!(f, "input does not parse ({one})"),
+            all => write!(f, "input does not parse ({} syntax errors)", all.len()),
+        }
+    }
+}
+
+impl std::error::Error for SyntaxErrors {}
+
+/// Returned by the post phase when input that parsed was formatted into
+/// something that does not.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NewSyntaxErrors(pub Vec<SyntaxError>);
+
+impl fmt::Display for NewSyntaxErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.as_slice() {
+            [first, ..] => write!(f, "formatted output does not parse ({first})"),
+            [] => write!(f, "formatted output does not parse"),
+        }
+    }
+}
+
+impl std::error::Error for NewSyntaxErrors {}
+
+/// Something worth telling the user that did not stop formatting.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Warning {
+    /// A `disable-next-line` directive names a rule that does not exist.
+    UnknownRule(UnknownRule),
+    /// The inline pipeline had not settled after `max_passes` passes; the
+    /// output of the last pass was kept.
+    NonConvergence(NonConvergence),
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownRule(w) => w.fmt(f),
+            Self::NonConvergence(w) => w.fmt(f),
+        }
+    }
+}
+
+impl From<UnknownRule> for Warning {
+    fn from(w: UnknownRule) -> Self {
+        Self::UnknownRule(w)
+    }
+}
+
+impl From<NonConvergence> for Warning {
+    fn from(w: NonConvergence) -> Self {
+        Self::NonConvergence(w)
+    }
+}
@@ -0,0 +1,103 @@
+// src/diff.rs
+//
+// Unified diffs of original vs. formatted text, for `--diff`, and the edits
+// that turn one into the other.
+
+use crate::engine::TextEdit;
+use owo_colors::OwoColorize;
+use similar::{ChangeTag, DiffOp, TextDiff};
+use std::fmt::Write;
+
+/// Lines of unchanged context around each hunk.
+const CONTEXT_LINES: usize = 3;
+
+/// Render a unified diff between `original` and `formatted`.
+///
+/// Returns an empty string when the two are identical. With `color` set,
+/// headers are bold, hunk ranges cyan, removals red and additions green.
+#[must_use]
+pub fn unified_diff(original: &str, formatted: &str, path: &str, color: bool) -> String {
+    let mut out = String::new();
+    if original == formatted {
+        return out;
+    }
+
+    let diff = TextDiff::from_lines(original, formatted);
+    let old_header = format!("--- {path}\t(original)");
+    let new_header = format!("+++ {path}\t(formatted)");
+    if color {
+        let _ = writeln!(out, "{}", old_header.bold());
+        let _ = writeln!(out, "{}", new_header.bold());
+    } else {
+        let _ = writeln!(out, "{old_header}");
+        let _ = writeln!(out, "{new_header}");
+    }
+
+    for hunk in diff
+        .unified_diff()
+        .context_radius(CONTEXT_LINES)
+        .iter_hunks()
+    {
+        let header = hunk.header().to_string();
+        if color {
+            let _ = writeln!(out, "{}", header.cyan());
+        } else {
+            let _ = writeln!(out, "{header}");
+        }
+
+        for change in hunk.iter_changes() {
+            let sign = match change.tag() {
+                ChangeTag::Delete => '-',
+                ChangeTag::Insert => '+',
+                ChangeTag::Equal => ' ',
+            };
+            let line = format!("{sign}{}", change.value().trim_end_matches('\n'));
+            match (color, sign) {
+                (true, '-') => {
+                    let _ = writeln!(out, "{}", line.red());
+                }
+                (true, '+') => {
+                    let _ = writeln!(out, "{}", line.green());
+                }
+                _ => {
+                    let _ = writeln!(out, "{line}");
+                }
+            }
+            if change.missing_newline() {
+                let _ = writeln!(out, "\\ No newline at end of file");
+            }
+        }
+    }
+    out
+}
+
+/// The edits that turn `original` into `formatted`, one per changed run of
+/// whole lines.
+///
+/// Offsets are into `original`. Unchanged lines are left out, so that an
+/// edit outside a format range or frozen span does not drag others with it.
+#[must_use]
+pub fn text_edits(original: &str, formatted: &str) -> Vec<TextEdit> {
+    let line_starts = |text: &str| {
+        let mut starts = vec![0];
+        starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+        if starts.last() != Some(&text.len()) {
+            starts.push(text.len());
+        }
+        starts
+    };
+    let (old, new) = (line_starts(original), line_starts(formatted));
+    TextDiff::from_lines(original, formatted)
+        .ops()
+        .iter()
+        .filter(|op| !matches!(op, DiffOp::Equal { .. }))
+        .map(|op| {
+            let (before, after) = (op.old_range(), op.new_range());
+            TextEdit {
+                start_byte: old[before.start],
+                end_byte: old[before.end],
+                replacement: formatted[new[after.start]..new[after.end]].to_string(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,153 @@
+// src/directives.rs
+//
+// Comments that switch formatting off for part of a file:
+//
+//     // sclang-format off
+//     ~table = [ 0.0,  0.25,  0.5,
+//                0.75, 1.0,   0.75 ];
+//     // sclang-format on
+//
+// Everything from the `off` comment to the end of the `on` comment is frozen
+// in `Ctx`, so no rule has to know about directives. The same goes for the
+// line after `// sclang-format-ignore-next-line`, or, for the rules it names,
+// after `// sclang-format-disable-next-line trailing_closures, ...`.
+
+use crate::engine::{ByteClass, ClassMap};
+use std::fmt;
+use std::ops::Range;
+use tree_sitter::Tree;
+
+/// First word of every directive comment.
+pub const DIRECTIVE: &str = "sclang-format";
+/// Leave the next line alone.
+pub const IGNORE_NEXT_LINE: &str = "sclang-format-ignore-next-line";
+/// Keep the rules listed after it off the next line.
+pub const DISABLE_NEXT_LINE: &str = "sclang-format-disable-next-line";
+
+/// Every `// ...` comment of `src` with its byte range, as the words after
+/// the slashes.
+fn line_comments<'a>(
+    src: &'a str,
+    classes: &'a ClassMap,
+) -> impl Iterator<Item = (Range<usize>, std::str::SplitWhitespace<'a>)> + 'a {
+    let is_comment = |i: usize| classes.class(i) == ByteClass::LineComment;
+    (0..src.len())
+        .filter(move |&i| is_comment(i) && (i == 0 || !is_comment(i - 1)))
+        .map(move |start| {
+            let end = (start..src.len())
+                .find(|&i| !is_comment(i))
+                .unwrap_or(src.len());
+            (start..end, src[start + 2..end].split_whitespace())
+        })
+}
+
+/// The regions between `// sclang-format off` and `// sclang-format on`,
+/// both comments included. An `off` without an `on` runs to the end of the
+/// input; an `on` outside a region does nothing.
+#[must_use]
+pub fn off_regions(src: &str, classes: &ClassMap) -> Vec<Range<usize>> {
+    let mut out = Vec::new();
+    let mut off: Option<usize> = None;
+    for (comment, mut words) in line_comments(src, classes) {
+        if words.next() != Some(DIRECTIVE) {
+            continue;
+        }
+        match (words.next(), off) {
+            (Some("off"), None) => off = Some(comment.start),
+            (Some("on"), Some(start)) => {
+                out.push(start..comment.end);
+                off = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(start) = off {
+        out.push(start..src.len());
+    }
+    out
+}
+
+/// A `// sclang-format-ignore-next-line` or
+/// `// sclang-format-disable-next-line` comment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NextLine {
+    /// The comment itself.
+    pub comment: Range<usize>,
+    /// 1-based line of the comment.
+    pub line: usize,
+    /// Rules named by `disable-next-line`, as written. Empty means every
+    /// rule, as for `ignore-next-line`.
+    pub rules: Vec<String>,
+}
+
+/// Every next-line directive of `src`, in order.
+#[must_use]
+pub fn next_line(src: &str, classes: &ClassMap) -> Vec<NextLine> {
+    line_comments(src, classes)
+        .filter_map(|(comment, mut words)| {
+            let rules = match words.next()? {
+                IGNORE_NEXT_LINE => Vec::new(),
+                DISABLE_NEXT_LINE => words
+                    .flat_map(|w| w.split(','))
+                    .filter(|n| !n.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+                _ => return None,
+            };
+            let line = src[..comment.start].matches('\n').count() + 1;
+            Some(NextLine {
+                comment,
+                line,
+                rules,
+            })
+        })
+        .collect()
+}
+
+/// What a next-line directive ending at byte `after` covers: the line after
+/// it, and the rest of any expression that starts there, so a rule never
+/// rewrites half of a call spanning several lines.
+#[must_use]
+pub fn next_line_span(tree: &Tree, src: &str, after: usize) -> Range<usize> {
+    let line_start = src[after..].find('\n').map_or(src.len(), |i| after + i + 1);
+    let line_end = src[line_start..]
+        .find('\n')
+        .map_or(src.len(), |i| line_start + i);
+    let first = line_start
+        + src[line_start..line_end]
+            .bytes()
+            .take_while(|&b| b == b' ' || b == b'\t')
+            .count();
+    let mut end = line_end;
+    let root = tree.root_node();
+    if first < line_end
+        && let Some(mut node) = root.descendant_for_byte_range(first, first)
+    {
+        while let Some(parent) = node.parent()
+            && parent != root
+            && parent.start_byte() == first
+        {
+            node = parent;
+        }
+        end = end.max(node.end_byte());
+    }
+    after..end
+}
+
+/// A `disable-next-line` directive naming a rule that does not exist.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownRule {
+    /// 1-based line of the directive.
+    pub line: usize,
+    pub name: String,
+}
+
+impl fmt::Display for UnknownRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}: unknown rule `{}` in `{DISABLE_NEXT_LINE}`",
+            self.line, self.name
+        )
+    }
+}
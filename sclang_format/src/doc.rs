@@ -1,134 +1,241 @@
-use std::fmt::Write;
+// src/doc.rs
+//
+// Pretty-printing Doc IR, after Wadler's "A prettier printer": a document
+// is text plus places where a line may break, grouped so that each group is
+// printed either entirely on one line or with all its breaks taken.
+
+use crate::engine::IndentStyle;
 
 /// Pretty-printing Doc IR
 #[derive(Clone, Debug)]
 pub enum Doc {
     Text(String),
-    Space,    // " "
-    SoftLine, // future: space or newline (for now renders as space)
-    HardLine, // newline + indent
-    Concat(Vec<Doc>),
-    Indent(Box<Doc>),
-    Group(Box<Doc>),
+    /// Always a single space.
+    Space,
+    /// A space if the enclosing group fits on one line, else a newline.
+    SoftLine,
+    /// Nothing if the enclosing group fits on one line, else a newline.
+    SoftBreak,
+    /// Always a newline; the enclosing groups can never be flat.
+    HardLine,
+    Concat(Vec<Self>),
+    /// Lines started inside are indented one level deeper.
+    Indent(Box<Self>),
+    Group(Box<Self>),
 }
 
 impl Doc {
-    pub fn text<S: Into<String>>(s: S) -> Doc {
-        Doc::Text(s.into())
+    #[must_use]
+    pub fn text<S: Into<String>>(s: S) -> Self {
+        Self::Text(s.into())
+    }
+    #[must_use]
+    pub const fn space() -> Self {
+        Self::Space
     }
-    pub fn space() -> Doc {
-        Doc::Space
+    #[must_use]
+    pub const fn softline() -> Self {
+        Self::SoftLine
     }
-    pub fn softline() -> Doc {
-        Doc::SoftLine
+    #[must_use]
+    pub const fn softbreak() -> Self {
+        Self::SoftBreak
     }
-    pub fn line() -> Doc {
-        Doc::HardLine
+    #[must_use]
+    pub const fn line() -> Self {
+        Self::HardLine
     }
-    pub fn concat(parts: Vec<Doc>) -> Doc {
-        Doc::Concat(parts)
+    #[must_use]
+    pub const fn concat(parts: Vec<Self>) -> Self {
+        Self::Concat(parts)
     }
-    pub fn indent(self) -> Doc {
-        Doc::Indent(Box::new(self))
+    #[must_use]
+    pub fn indent(self) -> Self {
+        Self::Indent(Box::new(self))
     }
-    pub fn group(self) -> Doc {
-        Doc::Group(Box::new(self))
+    #[must_use]
+    pub fn group(self) -> Self {
+        Self::Group(Box::new(self))
     }
 }
 
-pub fn join(sep: Doc, mut parts: Vec<Doc>) -> Doc {
-    use Doc::*;
-    if parts.is_empty() {
-        return Text(String::new());
-    }
-    let mut out = Vec::with_capacity(parts.len() * 2 - 1);
-    let last = parts.pop().unwrap();
-    for p in parts {
+/// `parts` with `sep` between each pair.
+#[must_use]
+pub fn join(sep: &Doc, parts: Vec<Doc>) -> Doc {
+    let mut out = Vec::with_capacity(parts.len() * 2);
+    for (i, p) in parts.into_iter().enumerate() {
+        if i > 0 {
+            out.push(sep.clone());
+        }
         out.push(p);
-        out.push(sep.clone());
     }
-    out.push(last);
-    Concat(out)
+    Doc::Concat(out)
 }
 
 #[derive(Clone, Copy, Debug)]
 pub struct RenderConfig {
-    pub width: usize,       // reserved for smarter wrapping later
-    pub indent_size: usize, // spaces per indent level
+    /// Column limit groups are fitted against. Widths are counted in
+    /// characters, a tab being one, as the width-aware rules do.
+    pub width: usize,
+    /// What one indentation level is written as.
+    pub indent_style: IndentStyle,
 }
+
 impl Default for RenderConfig {
     fn default() -> Self {
         Self {
-            width: 100,
-            indent_size: 2,
+            width: crate::engine::DEFAULT_MAX_WIDTH,
+            indent_style: IndentStyle::Spaces { width: 4 },
         }
     }
 }
 
-pub fn render(doc: &Doc, cfg: &RenderConfig) -> String {
-    let mut s = String::with_capacity(256);
-    let mut st = State {
-        out: &mut s,
-        cfg,
-        cur_indent: 0,
-        at_line_start: true,
-    };
-    st.render(doc);
-    s
+impl RenderConfig {
+    fn indent_unit(&self) -> String {
+        match self.indent_style {
+            IndentStyle::Tabs => "\t".to_string(),
+            IndentStyle::Spaces { width } => " ".repeat(width),
+        }
+    }
 }
 
-struct State<'a> {
-    out: &'a mut String,
-    cfg: &'a RenderConfig,
-    cur_indent: usize,
-    at_line_start: bool,
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
 }
 
-impl<'a> State<'a> {
-    fn render(&mut self, doc: &Doc) {
-        use Doc::*;
-        match doc {
-            Text(t) => self.write(t),
-            Space | SoftLine => self.write(" "),
-            HardLine => self.newline(),
-            Concat(v) => {
-                for d in v {
-                    self.render(d)
+/// Render `doc` within `cfg.width` columns where possible.
+///
+/// A group is broken only when it does not fit in the columns left on its
+/// line, counting up to the next possible break after it. Indentation is
+/// written lazily, so blank lines carry no trailing whitespace.
+#[must_use]
+pub fn render(doc: &Doc, cfg: &RenderConfig) -> String {
+    let unit = cfg.indent_unit();
+    let unit_width = unit.chars().count();
+    let mut out = String::with_capacity(256);
+    // Column on the current line, counting indentation not yet written.
+    let mut col = 0;
+    let mut at_line_start = true;
+    let mut stack: Vec<(usize, Mode, &Doc)> = vec![(0, Mode::Break, doc)];
+
+    while let Some((level, mode, doc)) = stack.pop() {
+        let mut write = |s: &str, out: &mut String| {
+            if s.is_empty() {
+                return;
+            }
+            if at_line_start {
+                for _ in 0..level {
+                    out.push_str(&unit);
                 }
+                col = level * unit_width;
+                at_line_start = false;
+            }
+            out.push_str(s);
+            col = s
+                .rfind('\n')
+                .map_or_else(|| col + s.chars().count(), |nl| s[nl + 1..].chars().count());
+        };
+        match doc {
+            Doc::Text(t) => write(t, &mut out),
+            Doc::Space => write(" ", &mut out),
+            Doc::SoftLine if mode == Mode::Flat => write(" ", &mut out),
+            Doc::SoftBreak if mode == Mode::Flat => {}
+            Doc::SoftLine | Doc::SoftBreak | Doc::HardLine => {
+                out.push('\n');
+                col = 0;
+                at_line_start = true;
+            }
+            Doc::Concat(parts) => {
+                stack.extend(parts.iter().rev().map(|d| (level, mode, d)));
+            }
+            Doc::Indent(d) => stack.push((level + 1, mode, d)),
+            Doc::Group(d) if mode == Mode::Flat => stack.push((level, Mode::Flat, d)),
+            Doc::Group(d) => {
+                let start = if at_line_start {
+                    level * unit_width
+                } else {
+                    col
+                };
+                let flat = fits(d, &stack, cfg.width.saturating_sub(start));
+                stack.push((level, if flat { Mode::Flat } else { Mode::Break }, d));
             }
-            Indent(d) | Group(d) => self.render(d),
         }
     }
-    fn write(&mut self, s: &str) {
-        if self.at_line_start {
-            let pad = self.cfg.indent_size * self.cur_indent;
-            let _ = write!(self.out, "{}", " ".repeat(pad));
-            self.at_line_start = false;
+    out
+}
+
+/// True if `doc` printed flat, followed by whatever `rest` prints up to its
+/// first line break, takes at most `width` columns.
+fn fits(doc: &Doc, rest: &[(usize, Mode, &Doc)], width: usize) -> bool {
+    let mut left = width;
+    let mut todo: Vec<(Mode, &Doc)> = vec![(Mode::Flat, doc)];
+    let mut rest = rest.iter().rev();
+    loop {
+        let (mode, doc) = match todo.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some(&(_, mode, doc)) => (mode, doc),
+                None => return true,
+            },
+        };
+        let text = match doc {
+            Doc::Text(t) => t.as_str(),
+            Doc::Space => " ",
+            Doc::SoftLine if mode == Mode::Flat => " ",
+            Doc::SoftBreak if mode == Mode::Flat => "",
+            Doc::SoftLine | Doc::SoftBreak => return true,
+            // A hard line ends the measured stretch, but the group holding
+            // it can never be flat.
+            Doc::HardLine => return mode == Mode::Break,
+            Doc::Concat(parts) => {
+                todo.extend(parts.iter().rev().map(|d| (mode, d)));
+                continue;
+            }
+            Doc::Indent(d) | Doc::Group(d) => {
+                todo.push((mode, d));
+                continue;
+            }
+        };
+        // Text spanning lines (block comments, strings) ends the stretch
+        // like a hard line does.
+        let (first_line, multiline) = text
+            .split_once('\n')
+            .map_or((text, false), |(l, _)| (l, true));
+        match left.checked_sub(first_line.chars().count()) {
+            Some(_) if multiline => return mode == Mode::Break,
+            Some(l) => left = l,
+            None => return false,
         }
-        let _ = write!(self.out, "{s}");
-    }
-    fn newline(&mut self) {
-        let _ = writeln!(self.out);
-        self.at_line_start = true;
     }
 }
 
 // tiny shorthands (optional)
 pub mod d {
     use super::Doc;
+    #[must_use]
     pub fn txt<S: Into<String>>(s: S) -> Doc {
         Doc::text(s)
     }
-    pub fn sp() -> Doc {
+    #[must_use]
+    pub const fn sp() -> Doc {
         Doc::space()
     }
-    pub fn sl() -> Doc {
+    #[must_use]
+    pub const fn sl() -> Doc {
         Doc::softline()
     }
-    pub fn ln() -> Doc {
+    #[must_use]
+    pub const fn sb() -> Doc {
+        Doc::softbreak()
+    }
+    #[must_use]
+    pub const fn ln() -> Doc {
         Doc::line()
     }
-    pub fn cat(v: Vec<Doc>) -> Doc {
+    #[must_use]
+    pub const fn cat(v: Vec<Doc>) -> Doc {
         Doc::concat(v)
     }
 }
@@ -1,22 +1,150 @@
-use anyhow::{Context, Result};
-use ropey::Rope;
+use ropey::{Rope, RopeSlice};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::fmt;
-use tree_sitter::{Language, Parser, Tree};
+use std::ops::Range;
+use tree_sitter::{InputEdit, Language, Parser, Point, Tree};
 
-pub mod ast;            // make `engine::ast` visible
+use crate::diagnostic::{self, SkippedRegion, SyntaxError, Warning};
+use crate::directives::{self, UnknownRule};
+use crate::error::Error;
+
+pub mod ast; // make `engine::ast` visible
 pub use ast::in_string_or_comment; // optional convenience re-export
+pub mod lex;
+pub use lex::{ByteClass, ClassMap};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct TextEdit {
     pub start_byte: usize,
     pub end_byte: usize,
     pub replacement: String,
 }
 
-#[derive(Clone, Copy, Debug)]
+impl TextEdit {
+    /// True if the edit touches `range`. Pure insertions count when they sit
+    /// anywhere within it, including either end.
+    #[must_use]
+    pub const fn intersects(&self, range: &Range<usize>) -> bool {
+        if self.start_byte == self.end_byte {
+            range.start <= self.start_byte && self.start_byte <= range.end
+        } else {
+            self.start_byte < range.end && self.end_byte > range.start
+        }
+    }
+}
+
+/// A rule asked for two edits that touch the same bytes in different ways.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EditConflict {
+    /// `Rule::name()` of the rule that produced the edits, if known.
+    pub rule: Option<&'static str>,
+    pub first: Range<usize>,
+    pub second: Range<usize>,
+}
+
+impl fmt::Display for EditConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(rule) = self.rule {
+            write!(f, "rule `{rule}` produced ")?;
+        }
+        write!(
+            f,
+            "overlapping edits at bytes {:?} and {:?}",
+            self.first, self.second
+        )
+    }
+}
+
+impl std::error::Error for EditConflict {}
+
+/// Sort `edits` into application order: by start, then by end, so an
+/// insertion at an offset goes before a replacement starting there whatever
+/// order they were emitted in. Only edits with the same range (several
+/// insertions at one offset) keep their emission order; identical ones
+/// collapse into the first, adjacent or not. Edits that merely touch are fine, and any real
+/// overlap is reported as the pair of conflicting byte ranges.
+fn normalize_edits(edits: &mut Vec<TextEdit>) -> Result<(), (Range<usize>, Range<usize>)> {
+    edits.sort_by_key(|e| (e.start_byte, e.end_byte));
+    let mut seen = HashSet::new();
+    edits.retain(|e| seen.insert((e.start_byte, e.end_byte, e.replacement.clone())));
+    let mut widest: Option<&TextEdit> = None;
+    for e in edits.iter() {
+        if let Some(w) = widest
+            && e.start_byte < w.end_byte
+        {
+            return Err((w.start_byte..w.end_byte, e.start_byte..e.end_byte));
+        }
+        if widest.is_none_or(|w| e.end_byte >= w.end_byte) {
+            widest = Some(e);
+        }
+    }
+    Ok(())
+}
+
+/// Where `pos` ends up after applying `edits` (sorted, non-overlapping).
+/// A position inside a replaced span snaps to the span's start, or to the
+/// end of its replacement when `right` is set; `right` also moves `pos` past
+/// an insertion made exactly at it.
+fn map_offset(pos: usize, edits: &[TextEdit], right: bool) -> usize {
+    let (mut added, mut removed) = (0usize, 0usize);
+    for e in edits {
+        let before = e.end_byte < pos || (e.end_byte == pos && (e.start_byte < pos || right));
+        if before {
+            added += e.replacement.len();
+            removed += e.end_byte - e.start_byte;
+        } else if e.start_byte < pos {
+            let start = e.start_byte + added - removed;
+            return if right {
+                start + e.replacement.len()
+            } else {
+                start
+            };
+        } else {
+            break;
+        }
+    }
+    pos + added - removed
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "style", rename_all = "lowercase")]
 pub enum IndentStyle {
     Tabs,
-    Spaces { width: usize },
+    Spaces {
+        #[serde(default = "default_indent_width")]
+        width: usize,
+    },
+}
+
+const fn default_indent_width() -> usize {
+    4
+}
+
+/// Column limit used by width-aware rules unless configured otherwise.
+pub const DEFAULT_MAX_WIDTH: usize = 80;
+
+/// Pipeline passes that may change the buffer before formatting is
+/// declared non-convergent.
+pub const DEFAULT_MAX_PASSES: usize = 10;
+
+/// `start` moved past `text`, counting rows the way tree-sitter does: only
+/// `\n` starts a new row, and columns are bytes.
+fn advance(start: Point, text: &str) -> Point {
+    text.rfind('\n').map_or(
+        Point {
+            row: start.row,
+            column: start.column + text.len(),
+        },
+        |last| Point {
+            row: start.row + text.bytes().filter(|&b| b == b'\n').count(),
+            column: text.len() - last - 1,
+        },
+    )
+}
+
+fn advance_over(start: Point, text: RopeSlice<'_>) -> Point {
+    text.chunks().fold(start, advance)
 }
 
 pub struct Ctx {
@@ -24,6 +152,26 @@ pub struct Ctx {
     parser: Parser,
     pub tree: Tree,
     pub indent_style: IndentStyle,
+    /// Longest line, in bytes, that width-aware rules may produce.
+    pub max_width: usize,
+    /// Check after every rule that it only changed layout (`--verify`).
+    pub verify: bool,
+    /// Most passes `rules::run_inline_with` makes over the pipeline that
+    /// change the buffer while looking for a fixed point.
+    pub max_passes: usize,
+    /// Fail instead of warning when the pipeline does not settle.
+    pub strict_convergence: bool,
+    range: Option<Range<usize>>,
+    frozen: Vec<Range<usize>>,
+    /// Spans only the named rules must leave alone.
+    suppressed: Vec<(Range<usize>, Vec<String>)>,
+    warnings: Vec<Warning>,
+    skipped: Vec<SkippedRegion>,
+    classes: ClassMap,
+    rule: Option<&'static str>,
+    revision: usize,
+    /// Syntax errors in the buffer as it was given to `new` or `reset`.
+    input_errors: usize,
 }
 
 impl fmt::Display for Ctx {
@@ -34,23 +182,169 @@ impl fmt::Display for Ctx {
 }
 
 impl Ctx {
-    pub fn new(src: String, lang: Language, indent_style: IndentStyle) -> Result<Self> {
+    /// # Errors
+    /// Fails with [`Error::Language`] if `lang` cannot be loaded, or with
+    /// [`Error::ParseAborted`] if tree-sitter gives up on `src`.
+    pub fn new(src: String, lang: Language, indent_style: IndentStyle) -> Result<Self, Error> {
         let mut parser = Parser::new();
-        parser.set_language(&lang).context("set_language failed")?;
-        let tree = parser.parse(src.as_str(), None).context("parse failed")?;
+        parser.set_language(&lang).map_err(Error::Language)?;
+        let tree = parser
+            .parse(src.as_str(), None)
+            .ok_or(Error::ParseAborted)?;
+        let input_errors = diagnostic::syntax_errors(&tree, &src).len();
         Ok(Self {
+            classes: ClassMap::scan(src.as_bytes()),
             rope: Rope::from_str(&src),
             parser,
             tree,
             indent_style,
+            max_width: DEFAULT_MAX_WIDTH,
+            verify: false,
+            max_passes: DEFAULT_MAX_PASSES,
+            strict_convergence: false,
+            range: None,
+            frozen: Vec::new(),
+            suppressed: Vec::new(),
+            warnings: Vec::new(),
+            skipped: Vec::new(),
+            rule: None,
+            revision: 0,
+            input_errors,
         })
     }
 
+    /// Replace the buffer with `src` and parse it from scratch, keeping the
+    /// parser (and its language) so one `Ctx` can format many files.
+    ///
+    /// # Errors
+    /// Fails with [`Error::ParseAborted`] if tree-sitter gives up on the
+    /// input.
+    pub fn reset(&mut self, src: &str) -> Result<(), Error> {
+        self.tree = self.parser.parse(src, None).ok_or(Error::ParseAborted)?;
+        self.rope = Rope::from_str(src);
+        self.classes = ClassMap::scan(src.as_bytes());
+        self.range = None;
+        self.frozen.clear();
+        self.suppressed.clear();
+        self.warnings.clear();
+        self.skipped.clear();
+        self.rule = None;
+        self.input_errors = diagnostic::syntax_errors(&self.tree, src).len();
+        Ok(())
+    }
+
+    /// Restrict formatting to `range` (byte offsets into the current buffer):
+    /// `apply_edits` drops every edit that does not intersect it. The range
+    /// is carried along as edits are applied. `None` lifts the restriction.
+    pub const fn set_range(&mut self, range: Option<Range<usize>>) {
+        self.range = range;
+    }
+
+    #[must_use]
+    pub fn range(&self) -> Option<Range<usize>> {
+        self.range.clone()
+    }
+
+    /// Protect `range` (byte offsets into the current buffer): `apply_edits`
+    /// drops every edit that touches it. Like the format range, frozen spans
+    /// are carried along as edits are applied.
+    pub fn freeze(&mut self, range: Range<usize>) {
+        self.frozen.push(range);
+    }
+
+    #[must_use]
+    pub fn frozen(&self) -> &[Range<usize>] {
+        &self.frozen
+    }
+
+    /// Freeze every `// sclang-format off` ... `// sclang-format on` region
+    /// of the buffer and every line after an `ignore-next-line` comment, and
+    /// keep the rules a `disable-next-line` comment names off the line after
+    /// it (see [`directives`]). Unknown rule names end up in
+    /// [`Self::warnings`].
+    pub fn freeze_directives(&mut self) {
+        let src = self.to_string();
+        self.frozen
+            .extend(directives::off_regions(&src, &self.classes));
+        let known = crate::rules::names();
+        for d in directives::next_line(&src, &self.classes) {
+            let span = directives::next_line_span(&self.tree, &src, d.comment.end);
+            for name in d.rules.iter().filter(|n| !known.contains(&n.as_str())) {
+                self.warn(UnknownRule {
+                    line: d.line,
+                    name: name.clone(),
+                });
+            }
+            if d.rules.is_empty() {
+                self.frozen.push(span);
+            } else {
+                self.suppressed.push((span, d.rules));
+            }
+        }
+    }
+
+    /// Problems found while formatting the buffer that did not stop it:
+    /// directive comments naming unknown rules (see
+    /// [`Self::freeze_directives`]), or a pipeline that did not settle.
+    #[must_use]
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    pub(crate) fn warn(&mut self, warning: impl Into<Warning>) {
+        self.warnings.push(warning.into());
+    }
+
+    /// Leave every top-level statement with a syntax error alone, and
+    /// remember which ones were skipped.
+    pub fn freeze_syntax_errors(&mut self) {
+        self.skipped = diagnostic::error_regions(&self.tree, &self.to_string());
+        for region in &self.skipped {
+            self.frozen.push(region.bytes.clone());
+        }
+    }
+
+    /// Statements frozen by [`Self::freeze_syntax_errors`], in offsets of
+    /// the buffer as it was then.
+    #[must_use]
+    pub fn skipped(&self) -> &[SkippedRegion] {
+        &self.skipped
+    }
+
+    /// Record which rule is about to run, so errors from `apply_edits` can
+    /// name it.
+    pub const fn set_rule(&mut self, rule: Option<&'static str>) {
+        self.rule = rule;
+    }
+
+    #[must_use]
+    pub const fn rule(&self) -> Option<&'static str> {
+        self.rule
+    }
+
+    /// How many syntax errors the buffer had before any edit.
+    #[must_use]
+    pub const fn input_errors(&self) -> usize {
+        self.input_errors
+    }
+
+    /// Bumped whenever `apply_edits` changes the buffer, so callers can
+    /// tell whether a rule did anything.
+    #[must_use]
+    pub const fn revision(&self) -> usize {
+        self.revision
+    }
+
     #[must_use]
     pub const fn indent_style(&self) -> IndentStyle {
         self.indent_style
     }
 
+    #[must_use]
+    pub const fn max_width(&self) -> usize {
+        self.max_width
+    }
+
     #[must_use]
     pub fn indent_unit(&self) -> String {
         match self.indent_style {
@@ -59,28 +353,125 @@ impl Ctx {
         }
     }
 
+    /// Token class of every byte in the buffer, kept in step with `tree`.
+    #[must_use]
+    pub const fn classes(&self) -> &ClassMap {
+        &self.classes
+    }
+
     #[must_use]
     pub fn bytes(&self) -> Vec<u8> {
         self.rope.to_string().into_bytes()
     }
 
-    pub fn apply_edits(&mut self, mut edits: Vec<TextEdit>) -> Result<()> {
+    /// Apply `edits` (offsets into the current buffer) and reparse. Edits
+    /// outside the format range, touching a frozen span (or one the current
+    /// rule is disabled on) or replacing text with itself are dropped.
+    ///
+    /// # Errors
+    /// Fails with [`Error::EditConflict`] if two edits overlap, or with
+    /// [`Error::ParseAborted`] if tree-sitter cannot reparse the result.
+    pub fn apply_edits(&mut self, mut edits: Vec<TextEdit>) -> Result<(), Error> {
+        if let Some(range) = &self.range {
+            edits.retain(|e| e.intersects(range));
+        }
+        edits.retain(|e| !self.frozen.iter().any(|f| e.intersects(f)));
+        if let Some(rule) = self.rule {
+            edits.retain(|e| {
+                !self
+                    .suppressed
+                    .iter()
+                    .any(|(span, rules)| e.intersects(span) && rules.iter().any(|r| r == rule))
+            });
+        }
+        edits.retain(|e| self.rope.byte_slice(e.start_byte..e.end_byte) != e.replacement.as_str());
         if edits.is_empty() {
             return Ok(());
         }
-        edits.sort_by_key(|e| e.start_byte);
-        for e in edits.into_iter().rev() {
+        if let Err((first, second)) = normalize_edits(&mut edits) {
+            return Err(EditConflict {
+                rule: self.rule,
+                first,
+                second,
+            }
+            .into());
+        }
+        if let Some(range) = &self.range {
+            self.range =
+                Some(map_offset(range.start, &edits, false)..map_offset(range.end, &edits, true));
+        }
+        for f in self
+            .frozen
+            .iter_mut()
+            .chain(self.suppressed.iter_mut().map(|(span, _)| span))
+        {
+            *f = map_offset(f.start, &edits, false)..map_offset(f.end, &edits, false);
+        }
+
+        // Positions for tree-sitter, in one forward pass over the old text.
+        // Applying back to front keeps each edit's coordinates valid.
+        let mut input_edits = Vec::with_capacity(edits.len());
+        let (mut at, mut pos) = (0, Point::default());
+        for e in &edits {
+            let start_position = advance_over(pos, self.rope.byte_slice(at..e.start_byte));
+            let old_end_position = advance_over(
+                start_position,
+                self.rope.byte_slice(e.start_byte..e.end_byte),
+            );
+            input_edits.push(InputEdit {
+                start_byte: e.start_byte,
+                old_end_byte: e.end_byte,
+                new_end_byte: e.start_byte + e.replacement.len(),
+                start_position,
+                old_end_position,
+                new_end_position: advance(start_position, &e.replacement),
+            });
+            (at, pos) = (e.end_byte, old_end_position);
+        }
+        for (e, input_edit) in edits.into_iter().zip(input_edits).rev() {
             let start_char = self.rope.byte_to_char(e.start_byte);
             let end_char = self.rope.byte_to_char(e.end_byte);
             self.rope.remove(start_char..end_char);
             if !e.replacement.is_empty() {
                 self.rope.insert(start_char, &e.replacement);
             }
+            self.tree.edit(&input_edit);
         }
+
+        self.classes = ClassMap::scan_rope(&self.rope);
+        self.revision += 1;
+        self.reparse()
+    }
+
+    /// Incrementally reparse after `tree` has been told about every edit,
+    /// feeding tree-sitter the rope chunk by chunk.
+    fn reparse(&mut self) -> Result<(), Error> {
+        let rope = &self.rope;
+        let mut read = |byte: usize, _: Point| -> &[u8] {
+            if byte >= rope.len_bytes() {
+                return &[];
+            }
+            let (chunk, chunk_start, _, _) = rope.chunk_at_byte(byte);
+            &chunk.as_bytes()[byte - chunk_start..]
+        };
+        self.tree = self
+            .parser
+            .parse_with_options(&mut read, Some(&self.tree), None)
+            .ok_or(Error::ParseAborted)?;
+        Ok(())
+    }
+
+    /// Parse the buffer again without reusing the old tree, to check that
+    /// incremental reparsing did not paper over anything.
+    ///
+    /// # Errors
+    /// Fails with [`Error::ParseAborted`] if tree-sitter gives up on the
+    /// input.
+    pub fn reparse_from_scratch(&mut self) -> Result<(), Error> {
         self.tree = self
             .parser
-            .parse(self.rope.to_string(), None)
-            .context("reparse failed")?;
+            .parse(self.to_string(), None)
+            .ok_or(Error::ParseAborted)?;
         Ok(())
     }
 
@@ -89,6 +480,12 @@ impl Ctx {
         self.rope.byte_slice(start..end).to_string()
     }
 
+    /// ERROR and MISSING nodes of the current tree, with their positions.
+    #[must_use]
+    pub fn syntax_errors(&self) -> Vec<SyntaxError> {
+        diagnostic::syntax_errors(&self.tree, &self.to_string())
+    }
+
     #[must_use]
     pub fn subtree_has_error(n: tree_sitter::Node) -> bool {
         if n.is_error() {
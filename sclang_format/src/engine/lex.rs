@@ -0,0 +1,341 @@
+// src/engine/lex.rs
+//
+// Byte-level token classes for SuperCollider source. Rules that scan bytes
+// use this instead of tracking comment/string state themselves, so they all
+// agree on what is code.
+
+use ropey::Rope;
+use std::ops::Range;
+use tree_sitter::Node;
+
+/// What kind of token a byte belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ByteClass {
+    /// Anything outside the literals and comments below, whitespace included.
+    Code,
+    /// `// ...`, up to but not including the newline.
+    LineComment,
+    /// `/* ... */`, nested ones included.
+    BlockComment,
+    /// `"..."`, quotes included.
+    String,
+    /// `'...'` or `\name`.
+    Symbol,
+    /// `$c`, `$\n`: the `$` plus the (possibly escaped) character.
+    Char,
+}
+
+impl ByteClass {
+    #[must_use]
+    pub const fn is_comment(self) -> bool {
+        matches!(self, Self::LineComment | Self::BlockComment)
+    }
+}
+
+/// One [`ByteClass`] per byte of a buffer.
+#[derive(Clone, Debug, Default)]
+pub struct ClassMap {
+    classes: Vec<ByteClass>,
+}
+
+impl ClassMap {
+    /// Classify every byte of `src`. Unterminated literals and comments run
+    /// to the end of the input.
+    #[must_use]
+    pub fn scan(src: &[u8]) -> Self {
+        Self {
+            classes: classify(&mut &*src),
+        }
+    }
+
+    /// [`Self::scan`] over a rope, read chunk by chunk rather than copied
+    /// into one buffer first.
+    #[must_use]
+    pub fn scan_rope(rope: &Rope) -> Self {
+        let mut chunks = Chunks {
+            rope,
+            chunk: &[],
+            start: 0,
+        };
+        Self {
+            classes: classify(&mut chunks),
+        }
+    }
+
+    /// Class of byte `i`; offsets past the end count as code.
+    #[must_use]
+    pub fn class(&self, i: usize) -> ByteClass {
+        self.classes.get(i).copied().unwrap_or(ByteClass::Code)
+    }
+
+    #[must_use]
+    pub fn is_code(&self, i: usize) -> bool {
+        self.class(i) == ByteClass::Code
+    }
+
+    #[must_use]
+    pub fn is_comment(&self, i: usize) -> bool {
+        self.class(i).is_comment()
+    }
+
+    /// True if any byte in `range` is part of a comment.
+    #[must_use]
+    pub fn has_comment(&self, range: Range<usize>) -> bool {
+        let end = range.end.min(self.classes.len());
+        self.classes
+            .get(range.start..end)
+            .is_some_and(|c| c.iter().any(|c| c.is_comment()))
+    }
+
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.classes.len()
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.classes.is_empty()
+    }
+}
+
+/// Bytes to classify, asked for one at a time and mostly in order.
+trait Source {
+    fn len(&self) -> usize;
+    fn at(&mut self, i: usize) -> Option<u8>;
+}
+
+impl Source for &[u8] {
+    fn len(&self) -> usize {
+        <[u8]>::len(self)
+    }
+
+    fn at(&mut self, i: usize) -> Option<u8> {
+        self.get(i).copied()
+    }
+}
+
+/// A rope read through the chunk holding the byte last asked for.
+struct Chunks<'a> {
+    rope: &'a Rope,
+    chunk: &'a [u8],
+    start: usize,
+}
+
+impl Source for Chunks<'_> {
+    fn len(&self) -> usize {
+        self.rope.len_bytes()
+    }
+
+    fn at(&mut self, i: usize) -> Option<u8> {
+        if !(self.start..self.start + self.chunk.len()).contains(&i) {
+            if i >= self.rope.len_bytes() {
+                return None;
+            }
+            let (chunk, start, _, _) = self.rope.chunk_at_byte(i);
+            self.chunk = chunk.as_bytes();
+            self.start = start;
+        }
+        Some(self.chunk[i - self.start])
+    }
+}
+
+fn classify(src: &mut impl Source) -> Vec<ByteClass> {
+    let len = src.len();
+    let mut classes = vec![ByteClass::Code; len];
+    let mut i = 0;
+    while let Some(b) = src.at(i) {
+        let start = i;
+        let class = match b {
+            b'/' if src.at(i + 1) == Some(b'/') => {
+                while src.at(i).is_some_and(|b| b != b'\n') {
+                    i += 1;
+                }
+                ByteClass::LineComment
+            }
+            b'/' if src.at(i + 1) == Some(b'*') => {
+                i = block_comment_end(src, i);
+                ByteClass::BlockComment
+            }
+            q @ (b'"' | b'\'') => {
+                i = quoted_end(src, i, q);
+                if q == b'"' {
+                    ByteClass::String
+                } else {
+                    ByteClass::Symbol
+                }
+            }
+            b'\\' if src.at(i + 1).is_some_and(is_ident_byte) => {
+                i += 1;
+                while src.at(i).is_some_and(is_ident_byte) {
+                    i += 1;
+                }
+                ByteClass::Symbol
+            }
+            b'$' if i + 1 < len => {
+                i += 1;
+                if src.at(i) == Some(b'\\') && i + 1 < len {
+                    i += 1;
+                }
+                i = char_end(src, i);
+                ByteClass::Char
+            }
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        classes[start..i].fill(class);
+    }
+    classes
+}
+
+/// The tokens under `root`, in source order, with their classes. The lexer,
+/// not the tree, decides what is a literal or a comment, so that a string
+/// torn apart by error recovery still comes out whole.
+#[must_use]
+pub fn tokens(root: Node<'_>, src: &str, classes: &ClassMap) -> Vec<(Range<usize>, ByteClass)> {
+    let mut leaves = Vec::new();
+    collect(root, src, &mut leaves);
+
+    let mut out = Vec::with_capacity(leaves.len());
+    let mut covered = 0;
+    for leaf in leaves {
+        if leaf.start < covered {
+            continue;
+        }
+        let class = classes.class(leaf.start);
+        if class == ByteClass::Code {
+            out.push((leaf, class));
+            continue;
+        }
+        covered = (leaf.start..classes.len())
+            .find(|&i| classes.class(i) != class)
+            .unwrap_or(classes.len())
+            .max(leaf.end);
+        out.push((leaf.start..covered, class));
+    }
+    out
+}
+
+/// Byte ranges of the leaves of `node` in source order. Text the parser did
+/// not make a clean token of (gaps between children, leaves mixing words with
+/// punctuation or whitespace, as error recovery produces) is split into words,
+/// numbers and single punctuation characters.
+fn collect(node: Node<'_>, src: &str, out: &mut Vec<Range<usize>>) {
+    if node.child_count() == 0 {
+        let text = src[node.byte_range()].as_bytes();
+        if text.is_empty() {
+            return;
+        }
+        if text.iter().all(|&b| is_word(b)) || text.iter().all(|&b| is_punct(b)) {
+            out.push(node.byte_range());
+        } else {
+            split_gap(src, node.byte_range(), out);
+        }
+        return;
+    }
+    let mut at = node.start_byte();
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        split_gap(src, at..child.start_byte().max(at), out);
+        collect(child, src, out);
+        at = at.max(child.end_byte());
+    }
+    split_gap(src, at..node.end_byte().max(at), out);
+}
+
+const fn is_word(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b >= 0x80
+}
+
+const fn is_punct(b: u8) -> bool {
+    !is_word(b) && !b.is_ascii_whitespace()
+}
+
+fn split_gap(src: &str, gap: Range<usize>, out: &mut Vec<Range<usize>>) {
+    let bytes = &src.as_bytes()[..gap.end];
+    let mut i = gap.start;
+    while i < gap.end {
+        let start = i;
+        if bytes[i].is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        // A byte order mark is not a token either.
+        if bytes[i..].starts_with("\u{feff}".as_bytes()) {
+            i += "\u{feff}".len();
+            continue;
+        }
+        i += 1;
+        if bytes[start].is_ascii_digit() {
+            i = number_end(bytes, i);
+        } else if is_word(bytes[start]) {
+            while i < gap.end && is_word(bytes[i]) {
+                i += 1;
+            }
+        }
+        out.push(start..i);
+    }
+}
+
+/// End (exclusive) of the number literal whose first digit precedes `i`:
+/// `1.5`, `1e-3`, `2r1010`, `0.5pi` stay one token, `1..4` does not.
+fn number_end(src: &[u8], mut i: usize) -> usize {
+    while i < src.len() {
+        let digit_at = |j: usize| src.get(j).is_some_and(u8::is_ascii_digit);
+        match src[i] {
+            b if is_word(b) => i += 1,
+            b'.' if digit_at(i + 1) => i += 2,
+            b'+' | b'-' if matches!(src[i - 1], b'e' | b'E') && digit_at(i + 1) => i += 2,
+            _ => break,
+        }
+    }
+    i
+}
+
+const fn is_ident_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+/// End (exclusive) of the block comment opening at `open`, honouring nesting.
+fn block_comment_end(src: &mut impl Source, open: usize) -> usize {
+    let mut depth = 0usize;
+    let mut i = open;
+    while let Some(b) = src.at(i) {
+        if b == b'/' && src.at(i + 1) == Some(b'*') {
+            depth += 1;
+            i += 2;
+        } else if b == b'*' && src.at(i + 1) == Some(b'/') {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    src.len()
+}
+
+/// End (exclusive) of the literal quoted with `quote` opening at `open`.
+fn quoted_end(src: &mut impl Source, open: usize, quote: u8) -> usize {
+    let mut i = open + 1;
+    while let Some(b) = src.at(i) {
+        match b {
+            b'\\' => i += 2,
+            b if b == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+    src.len()
+}
+
+/// End (exclusive) of the UTF-8 character starting at `i`.
+fn char_end(src: &mut impl Source, mut i: usize) -> usize {
+    i += 1;
+    while src.at(i).is_some_and(|b| (b & 0xC0) == 0x80) {
+        i += 1;
+    }
+    i
+}
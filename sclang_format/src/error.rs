@@ -0,0 +1,109 @@
+// src/error.rs
+//
+// The error type of the formatting entry points, so embedders can tell a
+// syntax error in the user's buffer from a bug in a rule without matching
+// on messages.
+
+use crate::diagnostic::{NewSyntaxErrors, SyntaxErrors};
+use crate::engine::EditConflict;
+use crate::rules::NonConvergence;
+use crate::verify::VerifyError;
+use std::fmt;
+
+/// Why formatting failed.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The grammar could not be loaded into the parser (an ABI mismatch
+    /// between the grammar and the tree-sitter runtime).
+    Language(tree_sitter::LanguageError),
+    /// tree-sitter gave up on the buffer and returned no tree at all.
+    ParseAborted,
+    /// The input does not parse and errors are not allowed; each error
+    /// carries its byte range and position.
+    Syntax(SyntaxErrors),
+    /// Input that parsed was formatted into something that does not.
+    BrokenOutput(NewSyntaxErrors),
+    /// A rule asked for overlapping edits.
+    EditConflict(EditConflict),
+    /// A rule changed more than layout (with `verify` on).
+    Verify(VerifyError),
+    /// The inline pipeline did not settle within `max_passes` (with
+    /// `strict_convergence` on; otherwise this is only a warning).
+    NonConvergence(NonConvergence),
+    /// A rule failed for a reason of its own.
+    Rule {
+        /// `Rule::name()` of the failing rule.
+        rule: &'static str,
+        source: anyhow::Error,
+    },
+    /// The options name an unknown phase or rule.
+    InvalidOptions(String),
+}
+
+impl Error {
+    /// `e`, returned by the rule `rule`, as the typed error it carries (an
+    /// edit conflict, a failed reparse), or else as a failure of that rule.
+    pub(crate) fn from_rule(rule: &'static str, e: anyhow::Error) -> Self {
+        match e.downcast::<Self>() {
+            Ok(e) => e,
+            Err(source) => Self::Rule { rule, source },
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Language(_) => write!(f, "could not load the SuperCollider grammar"),
+            Self::ParseAborted => write!(f, "tree-sitter gave up parsing the input"),
+            Self::Syntax(e) => e.fmt(f),
+            Self::BrokenOutput(e) => e.fmt(f),
+            Self::EditConflict(e) => e.fmt(f),
+            Self::Verify(e) => e.fmt(f),
+            Self::NonConvergence(e) => e.fmt(f),
+            Self::Rule { rule, .. } => write!(f, "rule `{rule}` failed"),
+            Self::InvalidOptions(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Language(e) => Some(e),
+            Self::Rule { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<SyntaxErrors> for Error {
+    fn from(e: SyntaxErrors) -> Self {
+        Self::Syntax(e)
+    }
+}
+
+impl From<NewSyntaxErrors> for Error {
+    fn from(e: NewSyntaxErrors) -> Self {
+        Self::BrokenOutput(e)
+    }
+}
+
+impl From<EditConflict> for Error {
+    fn from(e: EditConflict) -> Self {
+        Self::EditConflict(e)
+    }
+}
+
+impl From<VerifyError> for Error {
+    fn from(e: VerifyError) -> Self {
+        Self::Verify(e)
+    }
+}
+
+impl From<NonConvergence> for Error {
+    fn from(e: NonConvergence) -> Self {
+        Self::NonConvergence(e)
+    }
+}
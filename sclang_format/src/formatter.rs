@@ -0,0 +1,68 @@
+// src/formatter.rs
+//
+// A formatter that keeps its parser between inputs, for tools that format
+// many buffers (the CLI's workers, editor integrations).
+
+use crate::config::FormatOptions;
+use crate::diagnostic::{SkippedRegion, Warning};
+use crate::engine::Ctx;
+use crate::error::Error;
+use crate::{format_with_ctx, grammar};
+
+/// Formats any number of inputs with one set of options and one parser.
+///
+/// ```
+/// use sclang_format::{FormatOptions, Formatter, Phase};
+///
+/// let opts = FormatOptions::builder().phase(Phase::Post).build().unwrap();
+/// let mut formatter = Formatter::new(opts).unwrap();
+/// assert_eq!(formatter.format("x = 1;").unwrap(), "x = 1;\n");
+/// assert_eq!(formatter.format("y = 2;\n\n").unwrap(), "y = 2;\n");
+/// ```
+pub struct Formatter {
+    cx: Ctx,
+    opts: FormatOptions,
+}
+
+impl Formatter {
+    /// # Errors
+    /// Fails with [`Error::Language`] if the grammar cannot be loaded.
+    pub fn new(opts: FormatOptions) -> Result<Self, Error> {
+        let cx = Ctx::new(String::new(), grammar::language(), opts.indent_style)?;
+        Ok(Self { cx, opts })
+    }
+
+    #[must_use]
+    pub const fn options(&self) -> &FormatOptions {
+        &self.opts
+    }
+
+    /// Use `opts` from the next call to [`Self::format`] on, for instance
+    /// when inputs come from directories with different configs.
+    pub fn set_options(&mut self, opts: FormatOptions) {
+        self.opts = opts;
+    }
+
+    /// Format `src`.
+    ///
+    /// # Errors
+    /// As [`format_with_ctx`].
+    pub fn format(&mut self, src: &str) -> Result<String, Error> {
+        format_with_ctx(&mut self.cx, src, &self.opts)
+    }
+
+    /// Statements of the last input left alone because of syntax errors
+    /// (with `skip_errors`).
+    #[must_use]
+    pub fn skipped(&self) -> &[SkippedRegion] {
+        self.cx.skipped()
+    }
+
+    /// What went wrong with the last input without stopping formatting:
+    /// directive comments naming unknown rules, or a pipeline that did not
+    /// settle.
+    #[must_use]
+    pub fn warnings(&self) -> &[Warning] {
+        self.cx.warnings()
+    }
+}
@@ -3,35 +3,115 @@
 #![warn(clippy::cargo)]
 #![warn(clippy::nursery)]
 
+pub mod config;
+pub mod diagnostic;
+pub mod diff;
+pub mod directives;
+pub mod doc;
 pub mod engine;
+pub mod error;
+pub mod formatter;
 pub mod grammar;
+pub mod lsp;
+pub mod printer;
 pub mod rules;
+pub mod verify;
 
-use anyhow::Result;
-
+pub use config::{Engine, FormatOptions, FormatOptionsBuilder, FormatRange, IndentStrategy, Phase};
+pub use diagnostic::{NewSyntaxErrors, SkippedRegion, SyntaxError, SyntaxErrors, Warning};
 pub use engine::IndentStyle;
+pub use error::Error;
+pub use formatter::Formatter;
+
+/// Format `src` according to `opts` (usually loaded from `sclang-format.toml`).
+/// To format many inputs, keep a [`Formatter`] instead.
+///
+/// # Errors
+/// Fails with [`Error::Language`] if the grammar cannot be loaded, and as
+/// [`format_with_ctx`] does.
+pub fn format_source_with_options(src: &str, opts: &FormatOptions) -> Result<String, Error> {
+    Formatter::new(opts.clone())?.format(src)
+}
+
+/// Like [`format_source_with_options`], but reuses `cx` (and its parser)
+/// instead of building a new one. [`Formatter`] wraps this.
+///
+/// # Errors
+/// Fails with [`Error::Syntax`] if `src` does not parse and neither
+/// `opts.allow_errors` nor `opts.skip_errors` is set, with [`Error::Verify`]
+/// if `opts.verify` is set and a rule changed more than layout, with
+/// [`Error::NonConvergence`] if `opts.strict_convergence` is set and the
+/// pipeline does not settle within `opts.max_passes`, or with whatever else
+/// [`Error`] lists if parsing or a rule fails. With `skip_errors`, the
+/// statements that were left alone are listed by [`engine::Ctx::skipped`]
+/// afterwards; without `strict_convergence`, a pipeline that did not settle
+/// is among [`engine::Ctx::warnings`].
+pub fn format_with_ctx(
+    cx: &mut engine::Ctx,
+    src: &str,
+    opts: &FormatOptions,
+) -> Result<String, Error> {
+    cx.reset(src)?;
+    cx.indent_style = opts.indent_style;
+    cx.max_width = opts.max_width;
+    cx.verify = opts.verify;
+    cx.max_passes = opts.max_passes;
+    cx.strict_convergence = opts.strict_convergence;
+    cx.set_range(opts.range.map(|r| r.to_bytes(src)));
+    // Frozen before the pre phase: an `off` region is kept byte for byte.
+    cx.freeze_directives();
+    // The pre phase only evens out bytes, so it runs before syntax errors
+    // are looked for: a stray BOM or form feed should not count as one.
+    if opts.phase.includes(Phase::Pre) {
+        rules::run_pre_with(cx, &opts.rules)?;
+    }
+    if opts.skip_errors {
+        cx.freeze_syntax_errors();
+    } else if !opts.allow_errors && cx.tree.root_node().has_error() {
+        return Err(Error::Syntax(SyntaxErrors(cx.syntax_errors())));
+    }
+    if opts.phase.includes(Phase::Inline) {
+        run_inline(cx, opts)?;
+    }
+    if opts.phase.includes(Phase::Post) {
+        rules::run_post_with(cx, &opts.rules)?;
+    }
+    Ok(cx.to_string())
+}
+
+fn run_inline(cx: &mut engine::Ctx, opts: &FormatOptions) -> Result<(), Error> {
+    match opts.engine {
+        Engine::Rules => rules::run_inline_with(cx, &opts.inline_rules()),
+        Engine::Doc => printer::run(cx),
+    }
+}
 
+/// Run `phase` (`"pre"`, `"inline"`, `"post"` or `"all"`) over `src`.
+///
+/// # Errors
+/// Fails with [`Error::InvalidOptions`] on an unknown phase, and as
+/// [`format_source_with_options`] does.
 pub fn format_source_with_indent(
     src: &str,
     phase: &str,
     indent_style: IndentStyle,
-) -> Result<String> {
-    let mut cx = engine::Ctx::new(src.to_string(), grammar::language(), indent_style)?;
-    match phase {
-        "pre" => rules::run_pre(&mut cx)?,
-        "inline" => rules::run_inline(&mut cx)?,
-        "post" => rules::run_post(&mut cx)?,
-        "all" => {
-            rules::run_pre(&mut cx)?;
-            rules::run_inline(&mut cx)?;
-            rules::run_post(&mut cx)?;
-        }
-        _ => {}
-    }
-    Ok(cx.to_string())
+) -> Result<String, Error> {
+    let phase = phase
+        .parse()
+        .map_err(|e: anyhow::Error| Error::InvalidOptions(e.to_string()))?;
+    let opts = FormatOptions {
+        phase,
+        indent_style,
+        ..FormatOptions::default()
+    };
+    format_source_with_options(src, &opts)
 }
 
-// Backwards-compatible helper: default to 4-space indent.
-pub fn format_source(src: &str, phase: &str) -> Result<String> {
+/// [`format_source_with_indent`] with a 4-space indent, the default
+/// before indentation became configurable.
+///
+/// # Errors
+/// As [`format_source_with_indent`].
+pub fn format_source(src: &str, phase: &str) -> Result<String, Error> {
     format_source_with_indent(src, phase, IndentStyle::Spaces { width: 4 })
 }
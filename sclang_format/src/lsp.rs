@@ -0,0 +1,464 @@
+// src/lsp.rs
+//
+// `sclang-format lsp`: a Language Server Protocol server on stdin/stdout, so
+// editors can format through one long-lived process instead of piping every
+// buffer through the binary. It does formatting and nothing else: full-text
+// document sync, `textDocument/formatting` and
+// `textDocument/rangeFormatting`.
+//
+// Each document is formatted with the `sclang-format.toml` governing its
+// path, or its workspace folder for unsaved buffers. Without one, the
+// editor's tab size and tab/space choice stand in for `indent`.
+
+use crate::config::{self, FormatOptions, FormatRange};
+use crate::engine::{IndentStyle, TextEdit};
+use crate::{Error, Formatter, diff};
+use anyhow::{Context, Result};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// JSON-RPC error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_NOT_INITIALIZED: i64 = -32002;
+const REQUEST_FAILED: i64 = -32803;
+
+/// `window/logMessage` type for warnings.
+const MESSAGE_WARNING: u8 = 2;
+
+/// Read one message's body: `Content-Length` and other headers, a blank
+/// line, then that many bytes. Returns `None` at the end of the input.
+fn read_body(input: &mut impl BufRead) -> Result<Option<Vec<u8>>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            if length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            length = Some(
+                value
+                    .trim()
+                    .parse::<usize>()
+                    .with_context(|| format!("bad header `{line}`"))?,
+            );
+        }
+    }
+    let mut body = vec![0; length.unwrap_or_default()];
+    input.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+/// Read one message. Returns `None` at the end of the input.
+///
+/// # Errors
+/// Fails on IO errors, a bad `Content-Length`, or a body that is not JSON.
+pub fn read_message(input: &mut impl BufRead) -> Result<Option<Value>> {
+    match read_body(input)? {
+        Some(body) => Ok(Some(
+            serde_json::from_slice(&body).context("message is not JSON")?,
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Write `message` with its `Content-Length` header and flush.
+///
+/// # Errors
+/// Fails on IO errors.
+pub fn write_message(output: &mut impl Write, message: &Value) -> Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Serve requests from `input` until the client sends `exit` or closes it,
+/// writing responses and notifications to `output`.
+///
+/// Returns whether the client asked the server to shut down first; the
+/// protocol has the process exit with 1 if not.
+///
+/// # Errors
+/// Fails on IO errors, a bad `Content-Length`, or if the grammar cannot be
+/// loaded. A body that is not JSON gets a parse error response instead.
+pub fn serve(mut input: impl BufRead, mut output: impl Write) -> Result<bool> {
+    let mut server = Server::new()?;
+    while let Some(body) = read_body(&mut input)? {
+        let message: Value = match serde_json::from_slice(&body) {
+            Ok(message) => message,
+            Err(e) => {
+                let response = json!({
+                    "jsonrpc": "2.0",
+                    "id": null,
+                    "error": { "code": PARSE_ERROR, "message": format!("message is not JSON: {e}") },
+                });
+                write_message(&mut output, &response)?;
+                continue;
+            }
+        };
+        let Some(method) = message["method"].as_str() else {
+            // A response; this server never sends requests.
+            continue;
+        };
+        if method == "exit" {
+            return Ok(server.shut_down);
+        }
+        let params = &message["params"];
+        let response = if let Some(id) = message.get("id") {
+            Some(match server.request(method, params) {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                Err(ResponseError { code, message }) => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": code, "message": message },
+                }),
+            })
+        } else {
+            server.notify(method, params);
+            None
+        };
+        // Logged before the response, so they arrive with the edits.
+        for notification in server.outbox.drain(..).chain(response) {
+            write_message(&mut output, &notification)?;
+        }
+    }
+    Ok(server.shut_down)
+}
+
+/// An error response to a request.
+struct ResponseError {
+    code: i64,
+    message: String,
+}
+
+impl ResponseError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+struct Server {
+    formatter: Formatter,
+    /// Text of every open document, by URI.
+    documents: HashMap<String, String>,
+    /// Workspace folders, for documents without a path of their own.
+    roots: Vec<PathBuf>,
+    initialized: bool,
+    shut_down: bool,
+    /// Notifications to send once the current message is handled.
+    outbox: Vec<Value>,
+}
+
+impl Server {
+    fn new() -> Result<Self> {
+        Ok(Self {
+            formatter: Formatter::new(FormatOptions::default())?,
+            documents: HashMap::new(),
+            roots: Vec::new(),
+            initialized: false,
+            shut_down: false,
+            outbox: Vec::new(),
+        })
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, ResponseError> {
+        if method == "initialize" {
+            return Ok(self.initialize(params));
+        }
+        if !self.initialized {
+            return Err(ResponseError::new(
+                SERVER_NOT_INITIALIZED,
+                "initialize has not been called",
+            ));
+        }
+        if self.shut_down {
+            return Err(ResponseError::new(
+                INVALID_REQUEST,
+                "the server is shutting down",
+            ));
+        }
+        match method {
+            "shutdown" => {
+                self.shut_down = true;
+                Ok(Value::Null)
+            }
+            "textDocument/formatting" => self.format(params, None),
+            "textDocument/rangeFormatting" => {
+                let range = &params["range"];
+                self.format(params, Some((&range["start"], &range["end"])))
+            }
+            _ => Err(ResponseError::new(
+                METHOD_NOT_FOUND,
+                format!("unsupported method `{method}`"),
+            )),
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: &Value) {
+        let doc = &params["textDocument"];
+        let Some(uri) = doc["uri"].as_str() else {
+            return;
+        };
+        match method {
+            "textDocument/didOpen" => {
+                let text = doc["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.to_string(), text.to_string());
+            }
+            "textDocument/didChange" => {
+                let Some(text) = self.documents.get_mut(uri) else {
+                    return;
+                };
+                for change in params["contentChanges"].as_array().into_iter().flatten() {
+                    let new_text = change["text"].as_str().unwrap_or_default();
+                    // Full sync is asked for, but a range is honored if sent.
+                    let range = &change["range"];
+                    if range.is_object() {
+                        let start = offset(text, &range["start"]);
+                        let end = offset(text, &range["end"]).max(start);
+                        text.replace_range(start..end, new_text);
+                    } else {
+                        *text = new_text.to_string();
+                    }
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+            }
+            _ => {}
+        }
+    }
+
+    fn initialize(&mut self, params: &Value) -> Value {
+        let folders = params["workspaceFolders"].as_array().into_iter().flatten();
+        self.roots = folders
+            .filter_map(|f| f["uri"].as_str())
+            .chain(params["rootUri"].as_str())
+            .filter_map(uri_to_path)
+            .collect();
+        self.initialized = true;
+        json!({
+            "capabilities": {
+                // Full text on every change.
+                "textDocumentSync": 1,
+                "documentFormattingProvider": true,
+                "documentRangeFormattingProvider": true,
+            },
+            "serverInfo": {
+                "name": "sclang-format",
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+        })
+    }
+
+    /// The workspace folder holding the document at `uri`, the innermost
+    /// one if they nest, or else the first.
+    fn root(&self, uri: &str) -> Option<&Path> {
+        let path = uri_path(uri);
+        self.roots
+            .iter()
+            .filter(|root| path.as_ref().is_some_and(|p| p.starts_with(root)))
+            .max_by_key(|root| root.components().count())
+            .or_else(|| self.roots.first())
+            .map(PathBuf::as_path)
+    }
+
+    /// Options for the document at `uri`: its config file, or the editor's
+    /// indentation when there is none.
+    fn options(&self, uri: &str, editor: &Value) -> Result<FormatOptions, ResponseError> {
+        let start = uri_to_path(uri);
+        let (mut opts, source) = match start.as_deref().or_else(|| self.root(uri)) {
+            Some(start) => config::discover(start)
+                .map_err(|e| ResponseError::new(REQUEST_FAILED, format!("{e:#}")))?,
+            None => (FormatOptions::default(), None),
+        };
+        if source.is_none()
+            && let (Some(width), Some(spaces)) =
+                (editor["tabSize"].as_u64(), editor["insertSpaces"].as_bool())
+        {
+            opts.indent_style = if spaces {
+                match usize::try_from(width) {
+                    Ok(width @ 1..) => IndentStyle::Spaces { width },
+                    _ => {
+                        return Err(ResponseError::new(
+                            INVALID_PARAMS,
+                            format!("options.tabSize must be at least 1, not {width}"),
+                        ));
+                    }
+                }
+            } else {
+                IndentStyle::Tabs
+            };
+        }
+        Ok(opts)
+    }
+
+    fn format(
+        &mut self,
+        params: &Value,
+        range: Option<(&Value, &Value)>,
+    ) -> Result<Value, ResponseError> {
+        let Some(uri) = params["textDocument"]["uri"].as_str() else {
+            return Err(ResponseError::new(
+                INVALID_PARAMS,
+                "missing textDocument.uri",
+            ));
+        };
+        let Some(text) = self.documents.get(uri) else {
+            return Err(ResponseError::new(
+                INVALID_PARAMS,
+                format!("{uri} is not open"),
+            ));
+        };
+        let mut opts = self.options(uri, &params["options"])?;
+        if let Some((start, end)) = range {
+            opts.range = Some(FormatRange::Bytes {
+                start: offset(text, start),
+                end: offset(text, end),
+            });
+        }
+        self.formatter.set_options(opts);
+        let formatted = match self.formatter.format(text) {
+            Ok(formatted) => formatted,
+            // Likely half-typed code: formatting on save should not pop up
+            // an error every time, so there is just nothing to change.
+            Err(Error::Syntax(_)) => return Ok(Value::Null),
+            Err(e) => return Err(ResponseError::new(REQUEST_FAILED, e.to_string())),
+        };
+        for warning in self.formatter.warnings() {
+            self.outbox.push(json!({
+                "jsonrpc": "2.0",
+                "method": "window/logMessage",
+                "params": { "type": MESSAGE_WARNING, "message": format!("{uri}: {warning}") },
+            }));
+        }
+        let edits: Vec<Value> = diff::text_edits(text, &formatted)
+            .into_iter()
+            .map(|edit| {
+                let edit = shrink(text, edit);
+                json!({
+                    "range": {
+                        "start": position(text, edit.start_byte),
+                        "end": position(text, edit.end_byte),
+                    },
+                    "newText": edit.replacement,
+                })
+            })
+            .collect();
+        Ok(Value::Array(edits))
+    }
+}
+
+/// Trim what `edit` would replace with itself off both of its ends, so an
+/// edit that only adds a space is sent as just that.
+fn shrink(text: &str, mut edit: TextEdit) -> TextEdit {
+    let old = &text[edit.start_byte..edit.end_byte];
+    let new = edit.replacement.as_str();
+    let prefix = old
+        .char_indices()
+        .zip(new.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or_else(|| old.len().min(new.len()), |((i, _), _)| i);
+    let (old_rest, new_rest) = (&old[prefix..], &new[prefix..]);
+    let suffix = old_rest
+        .chars()
+        .rev()
+        .zip(new_rest.chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum::<usize>();
+    edit.replacement = new_rest[..new_rest.len() - suffix].to_string();
+    edit.start_byte += prefix;
+    edit.end_byte -= suffix;
+    edit
+}
+
+/// The LSP position of byte `at` of `text`: 0-based line, and column in
+/// UTF-16 code units.
+fn position(text: &str, at: usize) -> Value {
+    let before = &text[..at];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    json!({
+        "line": before.matches('\n').count(),
+        "character": before[line_start..].encode_utf16().count(),
+    })
+}
+
+/// The byte of `text` at LSP position `pos`, clamped to the end of its line
+/// and of the text.
+fn offset(text: &str, pos: &Value) -> usize {
+    let line = pos["line"].as_u64().unwrap_or_default();
+    let character = pos["character"].as_u64().unwrap_or_default();
+    let mut line_start = 0;
+    for _ in 0..line {
+        match text[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return text.len(),
+        }
+    }
+    let line_end = text[line_start..]
+        .find('\n')
+        .map_or(text.len(), |i| line_start + i);
+    let mut units = 0;
+    for (i, c) in text[line_start..line_end].char_indices() {
+        if units >= character {
+            return line_start + i;
+        }
+        units += c.len_utf16() as u64;
+    }
+    line_end
+}
+
+/// The path of a `file://` URI, percent-decoded.
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    uri.strip_prefix("file://").and_then(decode_path)
+}
+
+/// The path part of a URI of any scheme, as in `untitled:/x/a.scd` or
+/// `vscode-notebook-cell://host/x/a.scd#cell`, if it is absolute.
+fn uri_path(uri: &str) -> Option<PathBuf> {
+    let (_, rest) = uri.split_once(':')?;
+    let rest = rest.split(['?', '#']).next().unwrap_or_default();
+    let rest = match rest.strip_prefix("//") {
+        Some(authority) => &authority[authority.find('/')?..],
+        None => rest,
+    };
+    decode_path(rest)
+}
+
+/// A percent-encoded URI path as a file path, if it is absolute.
+fn decode_path(rest: &str) -> Option<PathBuf> {
+    let mut bytes = Vec::with_capacity(rest.len());
+    let mut iter = rest.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    let path = String::from_utf8(bytes).ok()?;
+    // `file:///C:/x` names `C:/x` on Windows.
+    let path = match path.strip_prefix('/') {
+        Some(rest) if cfg!(windows) && rest.get(1..2) == Some(":") => rest.to_string(),
+        _ => path,
+    };
+    Path::new(&path).is_absolute().then(|| PathBuf::from(path))
+}
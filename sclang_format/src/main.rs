@@ -3,12 +3,20 @@
 #![warn(clippy::cargo)]
 #![warn(clippy::nursery)]
 
-use anyhow::{Ok, Result};
-use clap::{Parser, ValueEnum};
+use anyhow::{Ok, Result, bail};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::fmt::Write as _;
 use std::fs;
-use std::io::{self, Read};
+use std::io::{self, IsTerminal, Read};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
 
-use sclang_format::{IndentStyle, format_source_with_indent};
+use sclang_format::config::{self, Engine, FormatOptions, FormatRange, IndentStrategy};
+use sclang_format::{Error, Formatter, IndentStyle, SyntaxErrors, diff, lsp, rules};
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
 enum IndentMode {
@@ -16,48 +24,609 @@ enum IndentMode {
     Spaces,
 }
 
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum EngineMode {
+    /// The rule pipeline.
+    Rules,
+    /// The AST-driven pretty printer (layout only).
+    Doc,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum IndentStrategyMode {
+    /// Re-derive every line's indentation from its nesting depth.
+    Ast,
+    /// Keep indentation as written, converted to the indent style.
+    Preserve,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// `auto` colors only when stdout is a terminal and `NO_COLOR` is unset.
+    fn enabled(self) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Never => false,
+            Self::Auto => io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+        }
+    }
+}
+
+/// Exit status when `--check` finds input that would be reformatted.
+const EXIT_WOULD_REFORMAT: u8 = 1;
+/// Exit status for IO, config and formatter errors.
+const EXIT_ERROR: u8 = 2;
+/// Exit status when an input has syntax errors and `--allow-errors` is unset.
+const EXIT_SYNTAX_ERROR: u8 = 3;
+
+/// Extensions picked up when walking directories.
+const SOURCE_EXTENSIONS: &[&str] = &["scd", "sc"];
+
 #[derive(Parser, Debug)]
-#[command(name = "sclang-format", version)]
+#[command(
+    name = "sclang-format",
+    version,
+    after_help = "Exit status: 0 on success, 1 if --check found files that would be \
+                  reformatted, 2 on errors, 3 if an input has syntax errors."
+)]
 struct Args {
-    path: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Files, directories (searched recursively for *.scd / *.sc) or glob
+    /// patterns. Reads stdin when omitted.
+    paths: Vec<String>,
+
+    /// Overrides `phase` from the config file (default: all).
+    #[arg(long, value_parser = config::PHASES.to_vec())]
+    phase: Option<String>,
 
-    #[arg(long, default_value="all", value_parser = ["pre","inline","post","all"])]
-    phase: String,
+    /// Overrides `engine` from the config file (default: rules).
+    #[arg(long, value_enum)]
+    engine: Option<EngineMode>,
 
     #[arg(long)]
     write: bool,
 
-    #[arg(long, value_enum, default_value_t = IndentMode::Spaces)]
-    indent_mode: IndentMode,
+    /// Write nothing; list inputs whose formatting would change and exit 1.
+    #[arg(long, conflicts_with = "write")]
+    check: bool,
+
+    /// Format input with syntax errors instead of reporting them and exiting 3.
+    #[arg(long)]
+    allow_errors: bool,
+
+    /// Format input with syntax errors, leaving the top-level statements that
+    /// contain them untouched.
+    #[arg(long, conflicts_with = "allow_errors")]
+    skip_errors: bool,
+
+    /// Check that no rule changed more than layout, and fail naming the rule
+    /// that did. On by default with --check.
+    #[arg(long)]
+    verify: bool,
+
+    /// Skip the verification --check does by default.
+    #[arg(long, conflicts_with = "verify")]
+    no_verify: bool,
+
+    /// Print a unified diff of original vs. formatted text instead of the output.
+    #[arg(long, conflicts_with = "write")]
+    diff: bool,
+
+    /// When to color `--diff` output.
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Overrides `indent.style` from the config file (default: spaces).
+    #[arg(long, value_enum)]
+    indent_mode: Option<IndentMode>,
+
+    /// Overrides `indent.width` from the config file (default: 4).
+    #[arg(long)]
+    indent_width: Option<usize>,
+
+    /// Overrides `indent_strategy` from the config file (default: preserve).
+    #[arg(long, value_enum)]
+    indent_strategy: Option<IndentStrategyMode>,
+
+    /// Overrides `max_width` from the config file (default: 80).
+    #[arg(long, value_name = "COLUMNS")]
+    max_width: Option<usize>,
+
+    /// Overrides `max_passes` from the config file (default: 10).
+    #[arg(long, value_name = "N")]
+    max_passes: Option<usize>,
+
+    /// Fail when formatting has not settled after the pass limit, instead of
+    /// warning and keeping the last pass.
+    #[arg(long)]
+    strict_convergence: bool,
+
+    /// Also run these rules (comma-separated `Rule::name()`s).
+    #[arg(long, value_name = "RULES", value_delimiter = ',')]
+    enable: Vec<String>,
+
+    /// Skip these rules.
+    #[arg(long, value_name = "RULES", value_delimiter = ',')]
+    disable: Vec<String>,
+
+    /// Run only these rules, ignoring `enable` / `disable`.
+    #[arg(long, value_name = "RULES", value_delimiter = ',')]
+    only: Vec<String>,
+
+    /// Only keep changes touching these lines (1-based, inclusive), e.g. 10:24.
+    #[arg(long, value_name = "START:END", value_parser = parse_lines, conflicts_with = "bytes")]
+    lines: Option<(usize, usize)>,
+
+    /// Only keep changes touching these byte offsets (end exclusive).
+    #[arg(long, value_name = "START:END", value_parser = parse_span)]
+    bytes: Option<(usize, usize)>,
+
+    /// Number of files formatted in parallel (default: number of CPUs).
+    #[arg(long, short = 'j')]
+    jobs: Option<NonZeroUsize>,
+
+    /// Use this config file instead of searching for `sclang-format.toml`.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Print the merged configuration (config file + flags) and exit.
+    #[arg(long)]
+    print_config: bool,
+
+    /// Print every rule in pipeline order and exit.
+    #[arg(long)]
+    list_rules: bool,
 
-    #[arg(long, default_value_t = 4)]
-    indent_width: usize,
+    /// Describe one rule, with a before/after example, and exit.
+    #[arg(long, value_name = "RULE")]
+    explain: Option<String>,
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
-    let src = if let Some(p) = &args.path {
-        fs::read_to_string(p)?
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Serve document and range formatting to an editor.
+    ///
+    /// Speaks the Language Server Protocol on stdin/stdout. Each document is
+    /// formatted with the config file governing its path.
+    Lsp,
+}
+
+impl Args {
+    /// Config file values for `start`, with any flags given on the command
+    /// line on top.
+    fn resolve_options(&self, start: &Path) -> Result<(FormatOptions, Option<PathBuf>)> {
+        let (mut opts, source) = if let Some(p) = &self.config {
+            (config::load(p)?, Some(p.clone()))
+        } else {
+            config::discover(start)?
+        };
+
+        if let Some(width) = self.max_width {
+            opts.max_width = width;
+        }
+        if let Some(passes) = self.max_passes {
+            opts.max_passes = passes;
+        }
+        if self.strict_convergence {
+            opts.strict_convergence = true;
+        }
+        if self.allow_errors {
+            opts.allow_errors = true;
+        }
+        if self.skip_errors {
+            opts.skip_errors = true;
+        }
+        opts.verify = (opts.verify || self.verify || self.check) && !self.no_verify;
+        if let Some(phase) = &self.phase {
+            opts.phase = phase.parse()?;
+        }
+        if let Some(engine) = self.engine {
+            opts.engine = match engine {
+                EngineMode::Rules => Engine::Rules,
+                EngineMode::Doc => Engine::Doc,
+            };
+        }
+        if let Some(strategy) = self.indent_strategy {
+            opts.indent_strategy = match strategy {
+                IndentStrategyMode::Ast => IndentStrategy::Ast,
+                IndentStrategyMode::Preserve => IndentStrategy::Preserve,
+            };
+        }
+        for name in &self.enable {
+            opts.rules.enable(name);
+        }
+        for name in &self.disable {
+            opts.rules.disable(name);
+        }
+        if !self.only.is_empty() {
+            opts.rules.only.clone_from(&self.only);
+        }
+        opts.range = match (self.lines, self.bytes) {
+            (Some((start, end)), _) => Some(FormatRange::Lines { start, end }),
+            (None, Some((start, end))) => Some(FormatRange::Bytes { start, end }),
+            (None, None) => None,
+        };
+        let file_width = match opts.indent_style {
+            IndentStyle::Tabs => None,
+            IndentStyle::Spaces { width } => Some(width),
+        };
+        opts.indent_style = match (self.indent_mode, file_width) {
+            (Some(IndentMode::Tabs), _) | (None, None) => IndentStyle::Tabs,
+            (Some(IndentMode::Spaces), _) | (None, Some(_)) => IndentStyle::Spaces {
+                width: self.indent_width.or(file_width).unwrap_or(4),
+            },
+        };
+        opts.check()?;
+        Ok((opts, source))
+    }
+}
+
+/// Parse `START:END` for `--lines` / `--bytes`.
+fn parse_span(s: &str) -> Result<(usize, usize), String> {
+    let (start, end) = s.split_once(':').ok_or("expected START:END")?;
+    let start: usize = start.trim().parse().map_err(|e| format!("{e}"))?;
+    let end: usize = end.trim().parse().map_err(|e| format!("{e}"))?;
+    if start > end {
+        return Err(format!("start {start} is after end {end}"));
+    }
+    Result::Ok((start, end))
+}
+
+/// Parse `START:END` for `--lines`, which are numbered from 1.
+fn parse_lines(s: &str) -> Result<(usize, usize), String> {
+    let (start, end) = parse_span(s)?;
+    if start == 0 {
+        return Err("lines are numbered from 1".to_string());
+    }
+    Result::Ok((start, end))
+}
+
+fn print_config(opts: &FormatOptions, source: Option<&Path>) -> Result<()> {
+    match source {
+        Some(p) => println!("# config: {}", p.display()),
+        None => println!("# config: <defaults>"),
+    }
+    print!("{}", opts.to_toml()?);
+    Ok(())
+}
+
+fn list_rules() {
+    let all = rules::all();
+    let width = all.iter().map(|r| r.name().len()).max().unwrap_or(0);
+    for r in &all {
+        let default = if r.default_enabled() { "" } else { " (off)" };
+        println!(
+            "{:width$}  {:11}  {}{default}",
+            r.name(),
+            r.category(),
+            r.description()
+        );
+    }
+}
+
+fn explain(name: &str) -> Result<()> {
+    let Some(rule) = rules::find(name) else {
+        bail!(
+            "unknown rule `{name}` (known rules: {})",
+            rules::names().join(", ")
+        );
+    };
+    let example = rule.example();
+    let mut out = String::new();
+    let _ = writeln!(out, "{}", rule.name());
+    let _ = writeln!(out, "  category: {}", rule.category());
+    let _ = writeln!(
+        out,
+        "  default:  {}",
+        if rule.default_enabled() { "on" } else { "off" }
+    );
+    let _ = writeln!(out, "\n{}\n", rule.description());
+    let _ = writeln!(out, "Before:");
+    for line in example.before.lines() {
+        let _ = writeln!(out, "    {line}");
+    }
+    let _ = writeln!(out, "\nAfter:");
+    for line in example.after.lines() {
+        let _ = writeln!(out, "    {line}");
+    }
+    print!("{out}");
+    Ok(())
+}
+
+enum Input {
+    Stdin,
+    File(PathBuf),
+}
+
+impl Input {
+    fn name(&self) -> String {
+        match self {
+            Self::Stdin => "<stdin>".to_string(),
+            Self::File(p) => p.display().to_string(),
+        }
+    }
+
+    /// Where config discovery starts for this input.
+    fn config_start(&self) -> PathBuf {
+        match self {
+            Self::Stdin => std::env::current_dir().unwrap_or_default(),
+            Self::File(p) => p.clone(),
+        }
+    }
+
+    fn read(&self) -> Result<String> {
+        match self {
+            Self::Stdin => {
+                let mut s = String::new();
+                io::stdin().read_to_string(&mut s)?;
+                Ok(s)
+            }
+            Self::File(p) => Ok(fs::read_to_string(p)?),
+        }
+    }
+}
+
+fn is_source_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| SOURCE_EXTENSIONS.contains(&e))
+}
+
+/// Recursively collect source files below `dir`, skipping hidden entries.
+fn walk_dir(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(fs::DirEntry::file_name);
+
+    let mut out = Vec::new();
+    for entry in entries {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            out.extend(walk_dir(&path)?);
+        } else if is_source_file(&path) {
+            out.push(path);
+        }
+    }
+    Ok(out)
+}
+
+fn expand_glob(pattern: &str) -> Result<Vec<PathBuf>> {
+    let mut out = Vec::new();
+    for entry in glob::glob(pattern)? {
+        let path = entry?;
+        if path.is_dir() {
+            out.extend(walk_dir(&path)?);
+        } else {
+            out.push(path);
+        }
+    }
+    if out.is_empty() {
+        bail!("no files match");
+    }
+    Ok(out)
+}
+
+/// Expand the positional arguments into the files to format. Arguments that
+/// cannot be expanded are reported and counted in `errors`.
+fn collect_inputs(paths: &[String], errors: &mut usize) -> Vec<Input> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    for arg in paths {
+        let path = Path::new(arg);
+        let found = if path.is_dir() {
+            walk_dir(path)
+        } else if arg.contains(['*', '?', '[']) {
+            expand_glob(arg)
+        } else {
+            Ok(vec![path.to_path_buf()])
+        };
+        match found {
+            Result::Ok(found) => {
+                for f in found {
+                    if !files.contains(&f) {
+                        files.push(f);
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("sclang-format: {arg}: {e:#}");
+                *errors += 1;
+            }
+        }
+    }
+    files.into_iter().map(Input::File).collect()
+}
+
+/// What formatting one input produced. Workers fill these in; the main
+/// thread prints them in input order so output is deterministic.
+struct Report {
+    changed: bool,
+    stdout: String,
+    stderr: String,
+}
+
+/// Format a single input in the mode selected on the command line, reusing
+/// the worker's formatter. Files are written here; anything meant for stdout is
+/// returned in the report.
+fn format_input(args: &Args, input: &Input, formatter: &mut Formatter) -> Result<Report> {
+    let (opts, _) = args.resolve_options(&input.config_start())?;
+    let src = input.read()?;
+    formatter.set_options(opts);
+    let out = formatter.format(&src)?;
+    let changed = out != src;
+    let name = input.name();
+
+    let mut stderr = String::new();
+    for region in formatter.skipped() {
+        let _ = writeln!(stderr, "sclang-format: {name}: skipped {region}");
+    }
+    for warning in formatter.warnings() {
+        let _ = writeln!(stderr, "sclang-format: {name}: warning: {warning}");
+    }
+    let mut stdout = String::new();
+    if args.diff {
+        stdout.push_str(&diff::unified_diff(&src, &out, &name, args.color.enabled()));
+    }
+    if args.check {
+        if changed {
+            let _ = writeln!(stdout, "would reformat: {name}");
+        }
+    } else if args.write
+        && let Input::File(p) = input
+    {
+        if changed {
+            fs::write(p, &out)?;
+        }
+    } else if !args.diff {
+        stdout = out;
+    }
+    Ok(Report {
+        changed,
+        stdout,
+        stderr,
+    })
+}
+
+/// Format `inputs` on a pool of `jobs` workers, each with its own
+/// [`Formatter`] and parser. Results are handed to `on_result` in input order.
+fn format_all(
+    args: &Args,
+    inputs: &[Input],
+    jobs: usize,
+    mut on_result: impl FnMut(&Input, Result<Report>),
+) {
+    let next = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, inputs.len().max(1)) {
+            let tx = tx.clone();
+            let next = &next;
+            scope.spawn(move || {
+                let mut formatter = Formatter::new(FormatOptions::default());
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(input) = inputs.get(i) else { break };
+                    let result = match &mut formatter {
+                        Result::Ok(formatter) => format_input(args, input, formatter),
+                        Err(e) => Err(anyhow::anyhow!("{e:#}")),
+                    };
+                    if tx.send((i, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(tx);
+
+        // Buffer out-of-order results until everything before them is done.
+        let mut pending: Vec<Option<Result<Report>>> = inputs.iter().map(|_| None).collect();
+        let mut emitted = 0;
+        for (i, result) in rx {
+            pending[i] = Some(result);
+            while let Some(result) = pending.get_mut(emitted).and_then(Option::take) {
+                on_result(&inputs[emitted], result);
+                emitted += 1;
+            }
+        }
+    });
+}
+
+fn run(args: &Args) -> Result<ExitCode> {
+    if let Some(Command::Lsp) = args.command {
+        let shut_down = lsp::serve(io::stdin().lock(), io::stdout().lock())?;
+        // The protocol asks for 1 when the client exits without a shutdown.
+        return Ok(if shut_down {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        });
+    }
+    if args.list_rules {
+        list_rules();
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(name) = &args.explain {
+        explain(name)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if args.print_config {
+        let start = args.paths.first().map_or_else(
+            || std::env::current_dir().unwrap_or_default(),
+            PathBuf::from,
+        );
+        let (opts, source) = args.resolve_options(&start)?;
+        print_config(&opts, source.as_deref())?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let mut errors = 0usize;
+    let inputs = if args.paths.is_empty() {
+        vec![Input::Stdin]
     } else {
-        let mut s = String::new();
-        io::stdin().read_to_string(&mut s)?;
-        s
+        collect_inputs(&args.paths, &mut errors)
     };
+    if inputs.len() > 1 && !(args.write || args.check || args.diff) {
+        bail!("formatting several files needs --write, --check or --diff");
+    }
+    if inputs.len() > 1 && (args.lines.is_some() || args.bytes.is_some()) {
+        bail!("--lines / --bytes apply to a single input");
+    }
 
-    let indent_style = match args.indent_mode {
-        IndentMode::Tabs => IndentStyle::Tabs,
-        IndentMode::Spaces => IndentStyle::Spaces {
-            width: args.indent_width,
+    let jobs = args
+        .jobs
+        .or_else(|| thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get);
+    let mut would_reformat = false;
+    let mut unparsable = 0usize;
+    format_all(args, &inputs, jobs, |input, result| match result {
+        Result::Ok(report) => {
+            would_reformat |= report.changed;
+            print!("{}", report.stdout);
+            eprint!("{}", report.stderr);
+        }
+        Err(e) => match e.downcast_ref::<Error>() {
+            Some(Error::Syntax(SyntaxErrors(found))) => {
+                let name = input.name();
+                for err in found {
+                    eprint!("{}", err.render(&name));
+                }
+                unparsable += 1;
+            }
+            _ => {
+                eprintln!("sclang-format: {}: {e:#}", input.name());
+                errors += 1;
+            }
         },
-    };
+    });
 
-    let out = format_source_with_indent(&src, &args.phase, indent_style)?;
-    if args.write
-        && let Some(p) = args.path
-    {
-        fs::write(p, &out)?;
-        return Ok(());
+    if errors > 0 {
+        Ok(ExitCode::from(EXIT_ERROR))
+    } else if unparsable > 0 {
+        Ok(ExitCode::from(EXIT_SYNTAX_ERROR))
+    } else if args.check && would_reformat {
+        Ok(ExitCode::from(EXIT_WOULD_REFORMAT))
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    match run(&args) {
+        Result::Ok(code) => code,
+        Err(e) => {
+            eprintln!("sclang-format: {e:#}");
+            ExitCode::from(EXIT_ERROR)
+        }
     }
-    print!("{out}");
-    Ok(())
 }
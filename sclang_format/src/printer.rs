@@ -0,0 +1,697 @@
+// src/printer.rs
+//
+// `--engine doc`: print the whole buffer from its syntax tree through the Doc
+// IR in one pass, instead of patching the text rule by rule. Only layout is
+// decided here; the rewrites the semantic rules make (`arg` to pipes,
+// trailing closures, ...) are left to the rule pipeline.
+//
+// Each node is laid out by its kind: blocks, code blocks, calls, collections,
+// events, parameter lists and binary expressions. A node with a syntax error
+// in it is taken apart into its tokens instead, paired by their brackets
+// with those of its neighbours: error recovery can put the two halves of a
+// pair in different nodes, but the brackets in the text still match.
+
+use crate::diff;
+use crate::doc::{Doc, RenderConfig, render};
+use crate::engine::{ByteClass, ClassMap, Ctx, lex};
+use crate::error::Error;
+use crate::verify::{self, Snapshot};
+use tree_sitter::{Node, Tree};
+
+/// Words that take a space before their parenthesis: `if (x)`, not `if(x)`.
+const KEYWORDS: &[&str] = &["if", "while", "for", "switch", "case"];
+
+#[derive(Clone, Copy, Debug)]
+struct Tok<'a> {
+    text: &'a str,
+    class: ByteClass,
+    start: usize,
+    /// Newlines between the previous token and this one.
+    breaks: usize,
+    /// No whitespace at all between the previous token and this one.
+    joined: bool,
+}
+
+impl Tok<'_> {
+    fn is(&self, text: &str) -> bool {
+        self.class == ByteClass::Code && self.text == text
+    }
+
+    const fn is_comment(&self) -> bool {
+        self.class.is_comment()
+    }
+
+    fn is_line_comment(&self) -> bool {
+        self.class == ByteClass::LineComment
+    }
+
+    fn doc(&self) -> Doc {
+        if self.is_line_comment() {
+            Doc::text(self.text.trim_end())
+        } else {
+            Doc::text(self.text)
+        }
+    }
+}
+
+/// How a token behaves for spacing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    /// Identifiers, keywords, numbers and literals.
+    Word,
+    Open,
+    Close,
+    Comma,
+    Semicolon,
+    Colon,
+    /// `.`, `..`, `...`
+    Dot,
+    /// Glued to what follows: `~x`, `#[`, `\sym`, `^x`, `` `x ``.
+    Prefix,
+    /// Binary (or unary) operators.
+    Op,
+}
+
+fn kind(t: Tok<'_>) -> Kind {
+    if t.class != ByteClass::Code {
+        return Kind::Word;
+    }
+    match t.text {
+        "(" | "[" | "{" => Kind::Open,
+        ")" | "]" | "}" => Kind::Close,
+        "," => Kind::Comma,
+        ";" => Kind::Semicolon,
+        ":" => Kind::Colon,
+        "." | ".." | "..." => Kind::Dot,
+        "~" | "#" | "\\" | "`" | "^" | "$" => Kind::Prefix,
+        s if s.starts_with(|c: char| c.is_alphanumeric() || c == '_') => Kind::Word,
+        _ => Kind::Op,
+    }
+}
+
+/// Does a space go between `a` and `b`? `pa` is the token before `a`, to
+/// tell a unary minus from a binary one.
+fn space_between(pa: Option<Tok<'_>>, a: Tok<'_>, b: Tok<'_>) -> bool {
+    let (ka, kb) = (kind(a), kind(b));
+    let unary = |t: Tok<'_>| {
+        matches!(t.text, "-" | "+" | "!")
+            && pa.is_none_or(|p| !matches!(kind(p), Kind::Word | Kind::Close))
+    };
+    let number =
+        |t: Tok<'_>| t.class == ByteClass::Code && t.text.starts_with(|c: char| c.is_ascii_digit());
+    match (ka, kb) {
+        // `1 . 5` is not `1.5`.
+        (Kind::Dot, _) if number(b) && !b.joined => true,
+        (_, Kind::Dot) if number(a) && !b.joined && !b.text.starts_with("..") => true,
+        (_, Kind::Comma | Kind::Semicolon | Kind::Colon | Kind::Dot)
+        | (Kind::Dot | Kind::Prefix, _) => false,
+        (Kind::Op, _) if unary(a) => false,
+        // An operator the tree split up, as `==` can be around an error.
+        (Kind::Op, Kind::Op) if b.joined => false,
+        (Kind::Comma | Kind::Semicolon | Kind::Colon | Kind::Op, _) | (_, Kind::Op) => true,
+        (Kind::Word, Kind::Open) if !b.is("{") => KEYWORDS.contains(&a.text),
+        (Kind::Word | Kind::Close, Kind::Open | Kind::Word | Kind::Prefix) => {
+            b.is("{") || kb != Kind::Open
+        }
+        _ => false,
+    }
+}
+
+#[derive(Clone)]
+enum Item<'a> {
+    Tok(Tok<'a>),
+    /// A node without errors, laid out by its kind.
+    Node {
+        node: Node<'a>,
+        first: Tok<'a>,
+        last: Tok<'a>,
+    },
+    Group(Group<'a>),
+}
+
+/// A bracketed run of items. `close` is missing if the input never closes it.
+#[derive(Clone)]
+struct Group<'a> {
+    /// Kind of the node the brackets belong to.
+    owner: &'static str,
+    open: Tok<'a>,
+    items: Vec<Item<'a>>,
+    close: Option<Tok<'a>>,
+}
+
+impl<'a> Item<'a> {
+    const fn first(&self) -> Tok<'a> {
+        match self {
+            Self::Tok(t) => *t,
+            Self::Node { first, .. } => *first,
+            Self::Group(g) => g.open,
+        }
+    }
+
+    fn last(&self) -> Tok<'a> {
+        match self {
+            Self::Tok(t) => *t,
+            Self::Node { last, .. } => *last,
+            Self::Group(g) => g
+                .close
+                .unwrap_or_else(|| g.items.last().map_or(g.open, Item::last)),
+        }
+    }
+
+    fn is(&self, text: &str) -> bool {
+        matches!(self, Self::Tok(t) if t.is(text))
+    }
+
+    const fn comment(&self) -> Option<Tok<'a>> {
+        match self {
+            Self::Tok(t) if t.is_comment() => Some(*t),
+            _ => None,
+        }
+    }
+
+    fn is_block(&self) -> bool {
+        match self {
+            Self::Node { node, .. } => node.kind() == "function_block",
+            Self::Group(g) => g.open.is("{"),
+            Self::Tok(_) => false,
+        }
+    }
+
+    const fn is_node(&self) -> bool {
+        matches!(self, Self::Node { .. })
+    }
+}
+
+impl Group<'_> {
+    /// The source broke a line directly inside this group.
+    fn multiline(&self) -> bool {
+        self.items.iter().any(|i| i.first().breaks > 0) || self.close.is_some_and(|c| c.breaks > 0)
+    }
+
+    /// Holds statements rather than a list: `( a; b )`.
+    fn has_statements(&self) -> bool {
+        self.items.iter().any(|i| i.is(";"))
+    }
+
+    fn close_doc(&self) -> Doc {
+        self.close.map_or_else(|| Doc::text(""), |c| c.doc())
+    }
+}
+
+fn lex_tree<'a>(tree: &Tree, src: &'a str, classes: &ClassMap) -> Vec<Tok<'a>> {
+    let mut end = 0;
+    lex::tokens(tree.root_node(), src, classes)
+        .into_iter()
+        .map(|(range, class)| {
+            let gap = &src[end..range.start];
+            end = range.end;
+            Tok {
+                text: &src[range.clone()],
+                class,
+                start: range.start,
+                breaks: gap.matches('\n').count(),
+                joined: gap.is_empty(),
+            }
+        })
+        .collect()
+}
+
+/// Pair the brackets among `items`, the children of an `owner` node. A
+/// closing bracket that matches nothing is kept as a plain token.
+fn nest<'a>(items: Vec<Item<'a>>, owner: &'static str) -> Vec<Item<'a>> {
+    let mut top = Vec::new();
+    let mut open: Vec<(Tok<'a>, Vec<Item<'a>>)> = Vec::new();
+    for item in items {
+        let Item::Tok(t) = item else {
+            open.last_mut().map_or(&mut top, |(_, v)| v).push(item);
+            continue;
+        };
+        let closes = |o: &Tok<'_>| matches!((o.text, t.text), ("(", ")") | ("[", "]") | ("{", "}"));
+        if t.class == ByteClass::Code && matches!(t.text, "(" | "[" | "{") {
+            open.push((t, Vec::new()));
+        } else if t.class == ByteClass::Code
+            && let Some((o, items)) = open.pop_if(|(o, _)| closes(o))
+        {
+            let group = Item::Group(Group {
+                owner,
+                open: o,
+                items,
+                close: Some(t),
+            });
+            open.last_mut().map_or(&mut top, |(_, v)| v).push(group);
+        } else {
+            open.last_mut()
+                .map_or(&mut top, |(_, v)| v)
+                .push(Item::Tok(t));
+        }
+    }
+    while let Some((o, items)) = open.pop() {
+        let group = Item::Group(Group {
+            owner,
+            open: o,
+            items,
+            close: None,
+        });
+        open.last_mut().map_or(&mut top, |(_, v)| v).push(group);
+    }
+    top
+}
+
+/// One line of a statement list.
+struct Line {
+    doc: Doc,
+    /// Preceded by a blank line in the source.
+    blank_before: bool,
+    /// Ends in a `//` comment, so nothing may follow it on the line.
+    open_ended: bool,
+}
+
+/// A line comment right after an opening bracket stays on the bracket's
+/// line: `{  // note`.
+fn bracket_comment<'i, 'a>(items: &'i [Item<'a>]) -> (Option<Tok<'a>>, &'i [Item<'a>]) {
+    match items.first().and_then(Item::comment) {
+        Some(c) if c.breaks == 0 && c.is_line_comment() => (Some(c), &items[1..]),
+        _ => (None, items),
+    }
+}
+
+fn trailing(comment: Option<Tok<'_>>) -> Doc {
+    comment.map_or_else(
+        || Doc::text(""),
+        |c| Doc::concat(vec![Doc::text("  "), c.doc()]),
+    )
+}
+
+fn join_lines(lines: Vec<Line>) -> Doc {
+    let mut out = Vec::with_capacity(lines.len() * 2);
+    for (i, line) in lines.into_iter().enumerate() {
+        if i > 0 {
+            out.push(Doc::line());
+            if line.blank_before {
+                out.push(Doc::line());
+            }
+        }
+        out.push(line.doc);
+    }
+    Doc::concat(out)
+}
+
+/// Nodes of one tree and the tokens of its buffer, printed by kind.
+struct Printer<'a> {
+    tokens: Vec<Tok<'a>>,
+}
+
+impl<'a> Printer<'a> {
+    /// Index of the first token starting at or after byte `at`.
+    fn token_at(&self, at: usize) -> usize {
+        self.tokens.partition_point(|t| t.start < at)
+    }
+
+    /// The children of `n`, bracket pairs nested. A child with a syntax
+    /// error in it is spliced in as its tokens; so are leaves, and literals
+    /// the grammar gives children of their own.
+    fn items(&self, n: Node<'a>) -> Vec<Item<'a>> {
+        let mut flat = Vec::new();
+        let mut i = self.token_at(n.start_byte());
+        let mut cursor = n.walk();
+        for child in n.children(&mut cursor) {
+            let start = self.token_at(child.start_byte()).max(i);
+            flat.extend(self.tokens[i..start].iter().map(|&t| Item::Tok(t)));
+            let end = self.token_at(child.end_byte()).max(start);
+            let whole = &self.tokens[start..end];
+            let literal = matches!(whole, [t] if t.class != ByteClass::Code);
+            match whole {
+                [first, .., last] | [first @ last]
+                    if child.child_count() > 0 && !child.has_error() && !literal =>
+                {
+                    flat.push(Item::Node {
+                        node: child,
+                        first: *first,
+                        last: *last,
+                    });
+                }
+                _ => flat.extend(whole.iter().map(|&t| Item::Tok(t))),
+            }
+            i = end;
+        }
+        let end = self.token_at(n.end_byte()).max(i);
+        flat.extend(self.tokens[i..end].iter().map(|&t| Item::Tok(t)));
+        nest(flat, n.kind())
+    }
+
+    /// `items`, with the nodes that hold a run of `sep`-separated parts
+    /// (a call's arguments, a block's statements) replaced by their children.
+    fn spliced(&self, items: &[Item<'a>], sep: &str) -> Vec<Item<'a>> {
+        let mut out = Vec::with_capacity(items.len());
+        for item in items {
+            if let Item::Node { node, .. } = item {
+                let inner = self.items(*node);
+                if inner.iter().any(|i| i.is(sep)) {
+                    out.extend(self.spliced(&inner, sep));
+                    continue;
+                }
+            }
+            out.push(item.clone());
+        }
+        out
+    }
+
+    fn item(&self, item: &Item<'a>, top: bool) -> Doc {
+        match item {
+            Item::Tok(t) => t.doc(),
+            Item::Node { node, .. } => self.node(*node, top),
+            Item::Group(g) => self.group(g, top),
+        }
+    }
+
+    /// A node without errors. Kinds that own brackets are laid out when
+    /// their group is reached, see [`Self::group`].
+    fn node(&self, n: Node<'a>, top: bool) -> Doc {
+        let items = self.items(n);
+        match n.kind() {
+            "binary_expression" => self.binary(&items),
+            "parameter_list" => self.parameters(&items),
+            _ => self.inline(&items.iter().collect::<Vec<_>>(), top),
+        }
+    }
+
+    /// Statements, one per line; comments on lines of their own stay there,
+    /// and at most one blank line is kept between statements. `top` is set
+    /// for the file itself.
+    fn statements(&self, items: &[Item<'a>], top: bool) -> Vec<Line> {
+        let items = self.spliced(items, ";");
+        let mut lines: Vec<Line> = Vec::new();
+        let mut cur: Vec<&Item<'a>> = Vec::new();
+        let finish = |cur: &mut Vec<&Item<'a>>, lines: &mut Vec<Line>| {
+            let Some(first) = cur.first() else {
+                return;
+            };
+            lines.push(Line {
+                doc: self.inline(cur, top),
+                blank_before: first.first().breaks > 1 && !lines.is_empty(),
+                open_ended: cur.last().is_some_and(|i| i.last().is_line_comment()),
+            });
+            cur.clear();
+        };
+        for item in &items {
+            // Two statements of the tree are two statements, `;` or not.
+            // Without a tree, SuperCollider code often leaves out the `;`
+            // after a closing brace at the end of a line; the next line is
+            // still a new statement.
+            let new_statement = cur.last().is_some_and(|last| {
+                (last.is_node() && item.is_node())
+                    || (last.is_block()
+                        && item.first().breaks > 0
+                        && kind(item.first()) == Kind::Word
+                        && !item.is("else"))
+            });
+            if new_statement {
+                finish(&mut cur, &mut lines);
+            }
+            if cur.is_empty()
+                && let Some(c) = item.comment()
+            {
+                match lines.last_mut() {
+                    Some(last) if c.breaks == 0 && !last.open_ended => {
+                        let gap = if c.is_line_comment() { "  " } else { " " };
+                        last.doc = Doc::concat(vec![
+                            std::mem::replace(&mut last.doc, Doc::text("")),
+                            Doc::text(gap),
+                            c.doc(),
+                        ]);
+                        last.open_ended = c.is_line_comment();
+                    }
+                    _ => lines.push(Line {
+                        doc: c.doc(),
+                        blank_before: c.breaks > 1 && !lines.is_empty(),
+                        open_ended: c.is_line_comment(),
+                    }),
+                }
+                continue;
+            }
+            cur.push(item);
+            if item.is(";") {
+                finish(&mut cur, &mut lines);
+            }
+        }
+        finish(&mut cur, &mut lines);
+        lines
+    }
+
+    /// Items of one statement or list element, spaced by their kinds.
+    /// `top` marks a statement of the file itself, where a leading `(` opens
+    /// a code block.
+    fn inline(&self, items: &[&Item<'a>], top: bool) -> Doc {
+        let mut out = Vec::with_capacity(items.len() * 2);
+        let (mut before, mut prev): (Option<Tok<'_>>, Option<Tok<'_>>) = (None, None);
+        for (i, item) in items.iter().enumerate() {
+            let first = item.first();
+            if let Some(p) = prev {
+                if p.is_line_comment() || first.is_comment() && first.breaks > 0 {
+                    out.push(Doc::line());
+                } else if first.is_line_comment() {
+                    out.push(Doc::text("  "));
+                } else if first.is_comment() || p.is_comment() || space_between(before, p, first) {
+                    out.push(Doc::space());
+                }
+            }
+            out.push(self.item(item, top && i == 0));
+            (before, prev) = (prev, Some(item.last()));
+        }
+        Doc::concat(out)
+    }
+
+    /// `a + b`, `a max: b`: a space on each side of the operator, whatever
+    /// the operands.
+    fn binary(&self, items: &[Item<'a>]) -> Doc {
+        match items {
+            [lhs, op @ .., rhs]
+                if !op.is_empty() && items.iter().all(|i| i.comment().is_none()) =>
+            {
+                Doc::concat(vec![
+                    self.item(lhs, false),
+                    Doc::space(),
+                    self.inline(&op.iter().collect::<Vec<_>>(), false),
+                    Doc::space(),
+                    self.item(rhs, false),
+                ])
+            }
+            _ => self.inline(&items.iter().collect::<Vec<_>>(), false),
+        }
+    }
+
+    /// `|a, b = 1|`, or `arg a, b = 1;` as a statement of its own.
+    fn parameters(&self, items: &[Item<'a>]) -> Doc {
+        match items {
+            [open, params @ .., close] if open.is("|") && close.is("|") => Doc::concat(vec![
+                Doc::text("|"),
+                self.elements(&params.iter().collect::<Vec<_>>()),
+                Doc::text("|"),
+            ]),
+            _ => self.inline(&items.iter().collect::<Vec<_>>(), false),
+        }
+    }
+
+    /// A bracket pair by the kind of node it belongs to. Brackets whose
+    /// node has no layout of its own (or that were paired from the tokens
+    /// of a node with errors) go by the bracket.
+    fn group(&self, g: &Group<'a>, top: bool) -> Doc {
+        match (g.owner, g.open.text) {
+            ("code_block", "(") => self.code_block(g, !top),
+            ("collection" | "event", "(" | "[") => self.list(g, g.multiline()),
+            (_, "{") => self.block(g),
+            (_, "(") if top || g.has_statements() => self.code_block(g, !top),
+            _ => self.list(g, false),
+        }
+    }
+
+    /// `( ... )` holding statements. A code block at the top of the file is
+    /// not indented, as is customary for regions meant to be evaluated as a
+    /// whole.
+    fn code_block(&self, g: &Group<'a>, indent: bool) -> Doc {
+        let (comment, items) = bracket_comment(&g.items);
+        let lines = self.statements(items, false);
+        if lines.is_empty() && comment.is_none() {
+            return Doc::concat(vec![g.open.doc(), g.close_doc()]);
+        }
+        let body = Doc::concat(vec![Doc::line(), join_lines(lines)]);
+        Doc::concat(vec![
+            g.open.doc(),
+            trailing(comment),
+            if indent { body.indent() } else { body },
+            Doc::line(),
+            g.close_doc(),
+        ])
+    }
+
+    /// `{ |params| statements }`: on one line if it has a single statement,
+    /// fits, and was written on one line; otherwise one statement per line.
+    fn block(&self, g: &Group<'a>) -> Doc {
+        let mut items = g.items.as_slice();
+        let mut head = vec![g.open.doc()];
+        match items.first() {
+            Some(params @ Item::Node { node, first, .. })
+                if node.kind() == "parameter_list" && first.is("|") =>
+            {
+                head.extend([Doc::space(), self.item(params, false)]);
+                items = &items[1..];
+            }
+            Some(i) if i.is("|") => {
+                if let Some(end) = items.iter().skip(1).position(|i| i.is("|")) {
+                    let params: Vec<&Item<'_>> = items[1..=end].iter().collect();
+                    head.extend([
+                        Doc::space(),
+                        Doc::text("|"),
+                        self.elements(&params),
+                        Doc::text("|"),
+                    ]);
+                    items = &items[end + 2..];
+                }
+            }
+            _ => {}
+        }
+        let (comment, items) = bracket_comment(items);
+        let lines = self.statements(items, false);
+        if lines.is_empty() && comment.is_none() {
+            if head.len() > 1 {
+                head.push(Doc::space());
+            }
+            head.push(g.close_doc());
+            return Doc::concat(head);
+        }
+        let broken = comment.is_some()
+            || lines.len() > 1
+            || lines.iter().any(|l| l.open_ended)
+            || g.multiline();
+        let sep = if broken { Doc::line() } else { Doc::softline() };
+        head.extend([
+            trailing(comment),
+            Doc::concat(vec![sep.clone(), join_lines(lines)]).indent(),
+            sep,
+            g.close_doc(),
+        ]);
+        if broken {
+            Doc::concat(head)
+        } else {
+            Doc::concat(head).group()
+        }
+    }
+
+    /// Comma-separated items laid out on one line: `a, b = 1`.
+    fn elements(&self, items: &[&Item<'a>]) -> Doc {
+        let parts = items
+            .split(|i| i.is(","))
+            .map(|e| self.inline(e, false))
+            .collect();
+        crate::doc::join(&Doc::text(", "), parts)
+    }
+
+    /// `(...)` and `[...]` lists: all on one line if they fit, else one
+    /// element per line. A function as the last argument hugs the brackets
+    /// instead: `f(x, { ... })`. With `broken` (a collection or event
+    /// written over several lines), one element per line whatever the
+    /// width.
+    fn list(&self, g: &Group<'a>, broken: bool) -> Doc {
+        let (comment, items) = bracket_comment(&g.items);
+        if items.is_empty() && comment.is_none() {
+            return Doc::concat(vec![g.open.doc(), g.close_doc()]);
+        }
+        // The grammar can wrap the elements in a node of their own.
+        let items = self.spliced(items, ",");
+        let elems: Vec<&[Item<'_>]> = items.split(|i| i.is(",")).collect();
+
+        let hugs = comment.is_none()
+            && !items.iter().any(|i| i.comment().is_some())
+            && elems
+                .last()
+                .is_some_and(|e| matches!(e, [i] if i.is_block()));
+        if hugs {
+            let all: Vec<&Item<'_>> = items.iter().collect();
+            return Doc::concat(vec![g.open.doc(), self.elements(&all), g.close_doc()]);
+        }
+
+        // Element i is followed by its comma (unless it is the last one) and
+        // by the comments that sat on the same line after it.
+        let mut out = vec![g.open.doc(), trailing(comment)];
+        let mut body = Vec::new();
+        let mut pending_break = comment.is_some() || broken;
+        let last = elems.len() - 1;
+        for (i, elem) in elems.iter().enumerate() {
+            let mut elem: &[Item<'_>] = elem;
+            // Comments right after the previous comma belong to that element.
+            while let Some(c) = elem.first().and_then(Item::comment)
+                && c.breaks == 0
+                && i > 0
+            {
+                body.push(trailing(Some(c)));
+                pending_break |= c.is_line_comment();
+                elem = &elem[1..];
+            }
+            if elem.is_empty() && i == last {
+                break;
+            }
+            body.push(if pending_break {
+                Doc::line()
+            } else if i == 0 {
+                Doc::softbreak()
+            } else {
+                Doc::softline()
+            });
+            pending_break = broken;
+            let elem: Vec<&Item<'_>> = elem.iter().collect();
+            body.push(self.inline(&elem, false));
+            pending_break |= elem.last().is_some_and(|i| i.last().is_line_comment());
+            if i < last {
+                body.push(Doc::text(","));
+            }
+        }
+        out.push(Doc::concat(body).indent());
+        out.push(if pending_break {
+            Doc::line()
+        } else {
+            Doc::softbreak()
+        });
+        out.push(g.close_doc());
+        Doc::concat(out).group()
+    }
+}
+
+/// The whole buffer as a document.
+fn document(tree: &Tree, src: &str, classes: &ClassMap) -> Doc {
+    let printer = Printer {
+        tokens: lex_tree(tree, src, classes),
+    };
+    let lines = printer.statements(&printer.items(tree.root_node()), true);
+    if lines.is_empty() {
+        return Doc::text("");
+    }
+    Doc::concat(vec![join_lines(lines), Doc::line()])
+}
+
+/// Print `src` (parsed as `tree`) from scratch.
+#[must_use]
+pub fn print(tree: &Tree, src: &str, cfg: &RenderConfig) -> String {
+    let classes = ClassMap::scan(src.as_bytes());
+    render(&document(tree, src, &classes), cfg)
+}
+
+/// Reprint the buffer of `cx` within `cx.max_width` and apply the result as
+/// edits, so the format range and frozen spans are honoured.
+///
+/// # Errors
+/// Fails with [`Error::Verify`] if `cx.verify` is set and the tokens
+/// changed, or if applying the edits fails.
+pub fn run(cx: &mut Ctx) -> Result<(), Error> {
+    let src = cx.to_string();
+    let cfg = RenderConfig {
+        width: cx.max_width,
+        indent_style: cx.indent_style,
+    };
+    let printed = render(&document(&cx.tree, &src, cx.classes()), &cfg);
+    let before = cx.verify.then(|| Snapshot::of(cx));
+    cx.apply_edits(diff::text_edits(&src, &printed))?;
+    if let Some(before) = before {
+        verify::compare(&before, &Snapshot::of(cx), None)?;
+    }
+    Ok(())
+}
@@ -1,7 +1,7 @@
 // src/rules/arg_to_pipe.rs
 
 use crate::engine::{Ctx, TextEdit};
-use crate::rules::Rule;
+use crate::rules::{Category, Example, Rule};
 use anyhow::Result;
 
 const fn is_space(b: u8) -> bool {
@@ -23,6 +23,21 @@ impl Rule for ArgToPipeParams {
         "arg_to_pipe_params"
     }
 
+    fn description(&self) -> &'static str {
+        "Rewrite a leading `arg a, b;` declaration as a `|a, b|` parameter list."
+    }
+
+    fn category(&self) -> Category {
+        Category::Semantic
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "f = {\n    arg a, b;\n    a + b\n};\n",
+            after: "f = { |a, b|\n    a + b\n};\n",
+        }
+    }
+
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
         let bytes = cx.bytes();
         let len = bytes.len();
@@ -1,8 +1,9 @@
 use anyhow::Result;
 use std::collections::HashSet;
-use tree_sitter::{Node, Query, QueryCursor, StreamingIterator};
+use tree_sitter::{Query, QueryCursor, StreamingIterator};
 
 use crate::engine::{Ctx, TextEdit};
+use crate::rules::{Category, Example};
 
 pub struct AddSpacesAroundAssignment;
 
@@ -15,20 +16,6 @@ impl AddSpacesAroundAssignment {
         !(prev == b'=' || next == b'=' || prev == b'<' || prev == b'>' || prev == b'!')
     }
 
-    #[inline]
-    fn in_comment_or_string(root: Node, byte: usize) -> bool {
-        let mut cur = root.descendant_for_byte_range(byte, byte + 1);
-        while let Some(n) = cur {
-            match n.kind() {
-                // conservative skip list; extend if your grammar uses different names
-                "comment" | "block_comment" | "line_comment" | "string" => return true,
-                _ => {}
-            }
-            cur = n.parent();
-        }
-        false
-    }
-
     #[inline]
     fn fix_one(bytes: &[u8], eq: usize, edits: &mut Vec<TextEdit>) {
         // Don't normalize spacing if we have K&R-style block attachment (= {)
@@ -94,8 +81,24 @@ impl crate::rules::Rule for AddSpacesAroundAssignment {
         "spaces_around_assignment"
     }
 
+    fn description(&self) -> &'static str {
+        "One space on each side of `=`."
+    }
+
+    fn category(&self) -> Category {
+        Category::Spacing
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "x=1;\n",
+            after: "x = 1;\n",
+        }
+    }
+
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
         let root = cx.tree.root_node();
+        let classes = cx.classes();
         let src = cx.bytes(); // Vec<u8>
         let src_slice: &[u8] = src.as_slice(); // &[u8] for TS & helpers
 
@@ -116,7 +119,7 @@ impl crate::rules::Rule for AddSpacesAroundAssignment {
             if !Self::is_assignment_eq(src_slice, eq) {
                 continue;
             }
-            if Self::in_comment_or_string(root, eq) {
+            if !classes.is_code(eq) {
                 continue;
             }
 
@@ -132,7 +135,7 @@ impl crate::rules::Rule for AddSpacesAroundAssignment {
             if !Self::is_assignment_eq(src_slice, i) {
                 continue;
             }
-            if Self::in_comment_or_string(root, i) {
+            if !classes.is_code(i) {
                 continue;
             }
             Self::fix_one(src_slice, i, &mut edits);
@@ -1,87 +1,296 @@
 // src/rules/ast_indent.rs
+//
+// `--indent-strategy ast`: throw away every line's indentation and derive it
+// again from how deeply the line is nested in the tree. A node nests the
+// lines inside it when its children include a pair of brackets: blocks,
+// collections and argument lists. Literals and comments come from the lexer,
+// as elsewhere.
+//
+// A tree with syntax errors cannot say what nests in what: recovery splits a
+// pair of brackets between ERROR nodes or closes one with a MISSING bracket
+// far from where the input does. Such input is re-indented by counting the
+// brackets in code instead, which stay balanced whatever the parser made of
+// them.
 
-use crate::engine::{Ctx, IndentStyle, TextEdit};
-use crate::rules::Rule;
+use crate::engine::{ByteClass, ClassMap, Ctx, IndentStyle, TextEdit};
+use crate::rules::{Category, Example, Rule};
 use anyhow::Result;
+use std::ops::Range;
+use tree_sitter::{Node, Tree};
 
-/// Simple comment/string tracker so we ignore braces inside them.
-#[derive(Clone, Copy, Debug)]
-struct CommentState {
-    in_line: bool,
-    in_block: bool,
-    in_single: bool,
-    in_double: bool,
-    prev: u8,
-}
-
-impl CommentState {
-    const fn new() -> Self {
-        CommentState {
-            in_line: false,
-            in_block: false,
-            in_single: false,
-            in_double: false,
-            prev: 0,
+pub struct IndentByAstLevel;
+
+/// Where a line sits among the brackets around it.
+struct Place {
+    /// Level of the lines inside the innermost brackets around the line.
+    inner: usize,
+    /// The level of the line that opened the brackets the line starts by
+    /// closing.
+    closes: Option<usize>,
+    /// The line carries on a statement or list element begun above it.
+    continued: bool,
+}
+
+/// How the lines of a buffer nest, asked about one line at a time in order.
+trait Nesting {
+    /// Where `line`, whose first byte after the indentation is `first`, sits.
+    fn place(&mut self, line: Range<usize>, first: usize) -> Place;
+
+    /// The contents of `line` sit at `here`.
+    fn placed(&mut self, line: Range<usize>, here: usize);
+}
+
+/// A pair of brackets among the children of `node`.
+#[derive(Clone, Copy)]
+struct Pair<'t> {
+    node: Node<'t>,
+    open: Node<'t>,
+    close: Node<'t>,
+}
+
+const fn is_open(kind: &str) -> bool {
+    matches!(kind.as_bytes(), b"(" | b"[" | b"{")
+}
+
+fn is_pair(open: &str, close: &str) -> bool {
+    matches!((open, close), ("(", ")") | ("[", "]") | ("{", "}"))
+}
+
+/// The bracket pairs among the children of `node`.
+fn pairs(node: Node<'_>) -> Vec<Pair<'_>> {
+    let mut out = Vec::new();
+    let mut open: Vec<Node<'_>> = Vec::new();
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if child.is_named() {
+            continue;
+        }
+        if is_open(child.kind()) {
+            open.push(child);
+        } else if let Some(o) = open.pop_if(|o| is_pair(o.kind(), child.kind())) {
+            out.push(Pair {
+                node,
+                open: o,
+                close: child,
+            });
         }
     }
+    out
+}
 
-    #[inline]
-    const fn in_comment_or_string(&self) -> bool {
-        self.in_line || self.in_block || self.in_single || self.in_double
+/// Nesting read from the ancestors of each line's first byte.
+struct FromTree<'a> {
+    tree: &'a Tree,
+    bytes: &'a [u8],
+    classes: &'a ClassMap,
+    /// Start of every line placed so far, and the level its contents sit at.
+    lines: Vec<(usize, usize)>,
+}
+
+impl<'a> FromTree<'a> {
+    /// The level of the line holding byte `at`.
+    fn level_at(&self, at: usize) -> usize {
+        let line = self.lines.partition_point(|&(start, _)| start <= at);
+        self.lines[line - 1].1
     }
 
-    #[inline]
-    fn step(&mut self, b: u8) {
-        // already inside something
-        if self.in_line {
-            if b == b'\n' {
-                self.in_line = false;
+    /// The innermost pair around byte `at`, and the pair `at` closes.
+    fn pairs_at(&self, at: usize) -> (Option<Pair<'a>>, Option<Pair<'a>>) {
+        let (mut inner, mut closed) = (None::<Pair<'a>>, None);
+        let mut node = self.tree.root_node().descendant_for_byte_range(at, at + 1);
+        while let Some(n) = node {
+            for pair in pairs(n) {
+                let close = pair.close.start_byte();
+                if pair.open.end_byte() > at || close < at {
+                    continue;
+                }
+                if close == at {
+                    closed = Some(pair);
+                } else if inner.is_none_or(|i| pair.open.start_byte() > i.open.start_byte()) {
+                    inner = Some(pair);
+                }
             }
-            self.prev = b;
-            return;
+            node = n.parent();
         }
+        (inner, closed)
+    }
 
-        if self.in_block {
-            if self.prev == b'*' && b == b'/' {
-                self.in_block = false;
-            }
-            self.prev = b;
-            return;
-        }
+    /// A `( ... )` code block at the top of the file, with the `(` alone on
+    /// its line: customarily not indented, as it is meant to be run whole.
+    fn is_code_block(&self, pair: Pair<'_>) -> bool {
+        let open = pair.open.start_byte();
+        let line_start = self.bytes[..open]
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+        let line_end = self.bytes[open..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(self.bytes.len(), |i| open + i);
+        pair.open.kind() == "("
+            && self.pairs_at(open).0.is_none()
+            && (line_start..line_end)
+                .filter(|&i| self.classes.is_code(i) && !is_ws(self.bytes[i]))
+                .eq([open])
+    }
 
-        if self.in_single {
-            if b == b'\'' && self.prev != b'\\' {
-                self.in_single = false;
+    /// The child of `pair`'s node that `at` belongs to: a statement of a
+    /// block, or an element of a list. A list the grammar wraps in a node of
+    /// its own (the `a, b` of a call's arguments) is looked into.
+    fn element(&self, pair: Option<Pair<'a>>, at: usize) -> Option<Node<'a>> {
+        let (node, list) = pair.map_or_else(
+            || (self.tree.root_node(), false),
+            |p| (p.node, p.open.kind() != "{"),
+        );
+        let containing = |n: Node<'a>| {
+            let mut cursor = n.walk();
+            n.children(&mut cursor)
+                .find(|c| c.start_byte() <= at && at < c.end_byte())
+        };
+        let has_commas = |n: Node<'a>| {
+            let mut cursor = n.walk();
+            n.children(&mut cursor)
+                .any(|c| !c.is_named() && c.kind() == ",")
+        };
+        let mut el = containing(node)?;
+        while list && el.start_byte() < at && has_commas(el) {
+            match containing(el) {
+                Some(inner) => el = inner,
+                None => break,
             }
-            self.prev = b;
-            return;
         }
+        Some(el)
+    }
+}
 
-        if self.in_double {
-            if b == b'"' && self.prev != b'\\' {
-                self.in_double = false;
-            }
-            self.prev = b;
-            return;
+impl Nesting for FromTree<'_> {
+    fn place(&mut self, line: Range<usize>, first: usize) -> Place {
+        let (inner, closed) = self.pairs_at(first);
+        Place {
+            inner: inner.map_or(0, |p| {
+                let opened_at = self.level_at(p.open.start_byte());
+                if self.is_code_block(p) {
+                    opened_at
+                } else {
+                    opened_at + 1
+                }
+            }),
+            closes: closed.map(|p| self.level_at(p.open.start_byte())),
+            continued: self
+                .element(inner, first)
+                .is_some_and(|el| el.start_byte() < line.start),
         }
+    }
+
+    fn placed(&mut self, line: Range<usize>, here: usize) {
+        self.lines.push((line.start, here));
+    }
+}
+
+/// A bracket still open at the start of the line being indented.
+struct Open {
+    /// Level of the line the bracket was opened on; its closing bracket
+    /// goes back there when it starts a line.
+    level: usize,
+    /// Level of the lines inside. Several brackets opened on one line
+    /// (`SynthDef(\x, {`) add a single level between them.
+    inner: usize,
+    /// `{ }`, or a top-level `( )` code block: statements are separated by
+    /// `;`, so a line ending in `,` (a `var` or `arg` list) continues.
+    block: bool,
+}
 
-        // not currently inside anything → maybe *enter* a comment/string
-        if self.prev == b'/' && b == b'/' {
-            self.in_line = true;
-        } else if self.prev == b'/' && b == b'*' {
-            self.in_block = true;
-        } else if b == b'\'' {
-            self.in_single = true;
-        } else if b == b'"' {
-            self.in_double = true;
+/// Nesting counted from the brackets in code, for trees with errors.
+struct FromBrackets<'a> {
+    bytes: &'a [u8],
+    classes: &'a ClassMap,
+    stack: Vec<Open>,
+    /// Last code byte of the last line holding code.
+    last: Option<u8>,
+}
+
+/// Last byte of a line that leaves its expression unfinished.
+const fn is_continued_by(b: u8) -> bool {
+    matches!(
+        b,
+        b'+' | b'-'
+            | b'*'
+            | b'/'
+            | b'%'
+            | b'&'
+            | b'<'
+            | b'>'
+            | b'='
+            | b'!'
+            | b'?'
+            | b'@'
+            | b'^'
+            | b'~'
+            | b':'
+            | b'.'
+    )
+}
+
+/// First byte of a line that carries on the previous one: a dot chain
+/// (`.play`) or a binary operator (`* env`).
+const fn continues(b: u8) -> bool {
+    // `~` and `:` never start a binary operator.
+    b == b'.' || (is_continued_by(b) && !matches!(b, b'~' | b':'))
+}
+
+const fn closes(b: u8) -> bool {
+    matches!(b, b')' | b']' | b'}')
+}
+
+const fn opens(b: u8) -> bool {
+    matches!(b, b'(' | b'[' | b'{')
+}
+
+impl Nesting for FromBrackets<'_> {
+    fn place(&mut self, line: Range<usize>, first: usize) -> Place {
+        let starts = (first < line.end).then(|| self.bytes[first]);
+        let block = self.stack.last().is_none_or(|o| o.block);
+        Place {
+            inner: self.stack.last().map_or(0, |o| o.inner),
+            closes: starts
+                .is_some_and(closes)
+                .then(|| self.stack.last().map_or(0, |o| o.level)),
+            continued: match (self.last, starts) {
+                (None, _) | (_, None) => false,
+                (Some(b), _) if opens(b) || b == b';' => false,
+                (Some(b','), _) => block,
+                (Some(b), _) if is_continued_by(b) => true,
+                (Some(b), Some(f)) => continues(f) && (b != b'}' || f == b'.'),
+            },
         }
+    }
 
-        self.prev = b;
+    // Brackets on this line shape the lines after it.
+    fn placed(&mut self, line: Range<usize>, here: usize) {
+        let (bytes, classes) = (self.bytes, self.classes);
+        let code: Vec<usize> = line
+            .filter(|&i| classes.is_code(i) && !is_ws(bytes[i]))
+            .collect();
+        let code_block = self.stack.is_empty() && matches!(code[..], [i] if bytes[i] == b'(');
+        for &i in &code {
+            match bytes[i] {
+                b if opens(b) => self.stack.push(Open {
+                    level: here,
+                    inner: if code_block { here } else { here + 1 },
+                    block: b == b'{' || code_block,
+                }),
+                b if closes(b) => {
+                    self.stack.pop();
+                }
+                _ => {}
+            }
+        }
+        if let Some(&i) = code.last() {
+            self.last = Some(bytes[i]);
+        }
     }
 }
 
-pub struct IndentByAstLevel;
-
 impl IndentByAstLevel {
     #[inline]
     fn make_indent(style: IndentStyle, level: usize) -> String {
@@ -90,134 +299,144 @@ impl IndentByAstLevel {
             IndentStyle::Spaces { width } => " ".repeat(level * width),
         }
     }
-}
 
-impl Rule for IndentByAstLevel {
-    fn name(&self) -> &'static str {
-        "indent_by_ast_level"
+    /// The level of every line of `bytes` that starts with code or a
+    /// comment, as `(line start, indentation end, level)`. Blank lines and
+    /// lines that continue a string or block comment are left out.
+    ///
+    /// A line sits one level deeper than the line that opened the innermost
+    /// brackets around it, and a line starting with a closing bracket goes
+    /// back to that line. A line in the middle of a statement or list
+    /// element (a dot chain, a long expression, a `var` list) gets one level
+    /// more.
+    fn levels(tree: &Tree, bytes: &[u8], classes: &ClassMap) -> Vec<(usize, usize, usize)> {
+        if tree.root_node().has_error() {
+            Self::levels_by(
+                FromBrackets {
+                    bytes,
+                    classes,
+                    stack: Vec::new(),
+                    last: None,
+                },
+                bytes,
+                classes,
+            )
+        } else {
+            Self::levels_by(
+                FromTree {
+                    tree,
+                    bytes,
+                    classes,
+                    lines: Vec::new(),
+                },
+                bytes,
+                classes,
+            )
+        }
     }
 
-    fn run(&self, cx: &mut Ctx) -> Result<usize> {
-        let src = cx.bytes(); // Vec<u8>
-        let bytes: &[u8] = src.as_slice();
-        let len = bytes.len();
+    fn levels_by(
+        mut nesting: impl Nesting,
+        bytes: &[u8],
+        classes: &ClassMap,
+    ) -> Vec<(usize, usize, usize)> {
+        let mut out = Vec::new();
+        // Comment lines wait for the next code line: they take its level,
+        // or that of the lines inside the bracket it closes.
+        let mut comments: Vec<(usize, usize)> = Vec::new();
 
-        let mut edits: Vec<TextEdit> = Vec::new();
-        let mut cs = CommentState::new();
+        let mut line_start = 0;
+        while line_start < bytes.len() {
+            let line_end = bytes[line_start..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(bytes.len(), |i| line_start + i);
+            let first = line_start
+                + bytes[line_start..line_end]
+                    .iter()
+                    .take_while(|&&b| is_ws(b))
+                    .count();
+            let in_literal = line_start > 0
+                && matches!(
+                    classes.class(line_start - 1),
+                    ByteClass::String | ByteClass::BlockComment | ByteClass::Symbol
+                );
 
-        // "Depth" here is brace depth: how many `{` blocks we are inside
-        // *at the start of the current line*.
-        let mut depth: isize = 0;
-
-        let mut i: usize = 0;
-        while i < len {
-            let line_start = i;
-
-            // Find end of line (exclusive).
-            let mut line_end = i;
-            while line_end < len && bytes[line_end] != b'\n' {
-                line_end += 1;
-            }
-
-            // Find first non-space/tab on the line.
-            let mut first_non_ws = line_start;
-            while first_non_ws < line_end {
-                let b = bytes[first_non_ws];
-                if b == b' ' || b == b'\t' {
-                    // whitespace doesn't affect comment/string state
-                    first_non_ws += 1;
-                } else {
-                    break;
-                }
-            }
-
-            // Blank / whitespace-only line: leave indentation alone, just
-            // advance over newline and keep comment state in sync.
-            if first_non_ws == line_end {
-                if line_end < len {
-                    cs.step(b'\n');
-                    i = line_end + 1;
+            let place = nesting.place(line_start..line_end, first);
+            let level = if first == line_end || in_literal {
+                None
+            } else if classes.is_comment(first) {
+                comments.push((line_start, first));
+                None
+            } else {
+                Some(
+                    place
+                        .closes
+                        .unwrap_or_else(|| place.inner + usize::from(place.continued)),
+                )
+            };
+            if let Some(level) = level {
+                let for_comments = if place.closes.is_some() {
+                    place.inner
                 } else {
-                    i = len;
+                    level
+                };
+                for (start, end) in std::mem::take(&mut comments) {
+                    out.push((start, end, for_comments));
                 }
-                continue;
+                out.push((line_start, first, level));
             }
+            nesting.placed(line_start..line_end, level.unwrap_or(place.inner));
+            line_start = line_end + 1;
+        }
+        let inner = nesting.place(bytes.len()..bytes.len(), bytes.len()).inner;
+        out.extend(comments.into_iter().map(|(start, end)| (start, end, inner)));
+        out
+    }
+}
 
-            // We now have some code on this line. We want "closing brace at
-            // start of line" lines to be indented one level *less* than the
-            // current depth (classic K&R behavior).
-            let first = bytes[first_non_ws];
+const fn is_ws(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\r')
+}
 
-            // Feed this byte into the comment/string state so we know whether
-            // it's actually part of code or inside a comment.
-            cs.step(first);
+impl Rule for IndentByAstLevel {
+    fn name(&self) -> &'static str {
+        "indent_by_ast_level"
+    }
 
-            // Effective depth for *this* line.
-            let mut effective_depth = depth;
-            let scan_start = first_non_ws + 1;
+    fn description(&self) -> &'static str {
+        "Re-derive every line's indentation from its nesting depth."
+    }
 
-            if !cs.in_comment_or_string() && first == b'}' {
-                // Line starts with a real closing brace: dedent this line.
-                if depth > 0 {
-                    depth -= 1;
-                }
-                if depth < 0 {
-                    depth = 0;
-                }
-                effective_depth = depth;
-                // We already processed this '}', so subsequent scanning starts
-                // after it.
-            } else if !cs.in_comment_or_string() && first == b'{' {
-                // Opening brace as first token on line: indent stays at the
-                // old depth for this line, but subsequent lines will see
-                // depth+1. So we count it *after* computing indentation.
-                effective_depth = if depth < 0 { 0 } else { depth };
-                depth += 1;
-            } else {
-                // Normal code, or comment/string start: no special treatment.
-                effective_depth = if depth < 0 { 0 } else { depth };
-            }
+    fn category(&self) -> Category {
+        Category::Spacing
+    }
 
-            // Compute the desired indentation string.
-            let new_indent = Self::make_indent(cx.indent_style, effective_depth as usize);
-
-            // Existing indentation slice (spaces/tabs only).
-            let existing_indent = &bytes[line_start..first_non_ws];
-            if existing_indent != new_indent.as_bytes() {
-                edits.push(TextEdit {
-                    start_byte: line_start,
-                    end_byte: first_non_ws,
-                    replacement: new_indent,
-                });
-            }
+    fn example(&self) -> Example {
+        Example {
+            before: "{\nx = 1\n* 2;\n  }\n",
+            after: "{\n    x = 1\n        * 2;\n}\n",
+        }
+    }
 
-            // Now scan the *rest* of the line for additional braces, updating
-            // depth for subsequent lines. We ignore braces inside comments
-            // and strings.
-            let mut k = scan_start;
-            while k < line_end {
-                let b = bytes[k];
-                cs.step(b);
-                if !cs.in_comment_or_string() {
-                    if b == b'{' {
-                        depth += 1;
-                    } else if b == b'}' {
-                        if depth > 0 {
-                            depth -= 1;
-                        }
-                    }
-                }
-                k += 1;
-            }
+    // Rewrites indentation wholesale; switched on by `--indent-strategy ast`.
+    fn default_enabled(&self) -> bool {
+        false
+    }
 
-            // Finally feed the newline into the comment state and advance.
-            if line_end < len {
-                cs.step(b'\n');
-                i = line_end + 1;
-            } else {
-                i = len;
-            }
-        }
+    fn run(&self, cx: &mut Ctx) -> Result<usize> {
+        let bytes = cx.bytes();
+        let edits: Vec<TextEdit> = Self::levels(&cx.tree, &bytes, cx.classes())
+            .into_iter()
+            .filter_map(|(start, end, level)| {
+                let indent = Self::make_indent(cx.indent_style, level);
+                (bytes[start..end] != *indent.as_bytes()).then_some(TextEdit {
+                    start_byte: start,
+                    end_byte: end,
+                    replacement: indent,
+                })
+            })
+            .collect();
 
         let n = edits.len();
         if n > 0 {
@@ -226,4 +445,3 @@ impl Rule for IndentByAstLevel {
         Ok(n)
     }
 }
-
@@ -1,9 +1,8 @@
 // src/rules/binary_ops.rs
 
 use crate::engine::{Ctx, TextEdit};
-use crate::rules::Rule;
+use crate::rules::{Category, Example, Rule};
 use anyhow::Result;
-use tree_sitter::Node;
 
 /// Binary operators we normalize.
 /// We handle:
@@ -27,19 +26,6 @@ const fn is_ascii_op_char(b: u8) -> bool {
     )
 }
 
-/// Super-cautious check: are we inside a comment or string at `byte`?
-fn in_comment_or_string(root: Node, byte: usize) -> bool {
-    let mut cur = root.descendant_for_byte_range(byte, byte + 1);
-    while let Some(n) = cur {
-        match n.kind() {
-            "comment" | "block_comment" | "line_comment" | "string" => return true,
-            _ => {}
-        }
-        cur = n.parent();
-    }
-    false
-}
-
 fn is_unary_plus_minus(bytes: &[u8], i: usize) -> bool {
     let c = bytes[i];
     if c != b'+' && c != b'-' {
@@ -107,8 +93,23 @@ impl Rule for AddSpacesAroundBinaryOps {
         "spaces_around_binary_ops"
     }
 
+    fn description(&self) -> &'static str {
+        "One space on each side of binary operators."
+    }
+
+    fn category(&self) -> Category {
+        Category::Spacing
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "x = a+b*2;\n",
+            after: "x = a + b * 2;\n",
+        }
+    }
+
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
-        let root = cx.tree.root_node();
+        let classes = cx.classes();
         let src = cx.bytes();
         let len = src.len();
         let bytes: &[u8] = &src;
@@ -120,7 +121,7 @@ impl Rule for AddSpacesAroundBinaryOps {
             let b = bytes[i];
 
             // Skip if in comment/string.
-            if in_comment_or_string(root, i) {
+            if !classes.is_code(i) {
                 i += 1;
                 continue;
             }
@@ -1,7 +1,7 @@
 // src/rules/block_brace.rs
 
 use crate::engine::{Ctx, TextEdit};
-use crate::rules::Rule;
+use crate::rules::{Category, Example, Rule};
 use anyhow::Result;
 
 const fn is_space(b: u8) -> bool {
@@ -12,21 +12,6 @@ const fn is_newline(b: u8) -> bool {
     b == b'\n' || b == b'\r'
 }
 
-/// Check if the quote at `i` is escaped by an odd number of backslashes.
-fn is_escaped(bytes: &[u8], i: usize) -> bool {
-    let mut count = 0;
-    let mut j = i;
-    while j > 0 {
-        j -= 1;
-        if bytes[j] == b'\\' {
-            count += 1;
-        } else {
-            break;
-        }
-    }
-    count % 2 == 1
-}
-
 fn fix_before_brace(bytes: &[u8], brace: usize, edits: &mut Vec<TextEdit>) {
     if brace == 0 {
         return;
@@ -68,78 +53,35 @@ impl Rule for BlockBraceSpacing {
         "block_brace_spacing"
     }
 
+    fn description(&self) -> &'static str {
+        "One space between a method name and a trailing `{`."
+    }
+
+    fn category(&self) -> Category {
+        Category::Spacing
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "a.do{ |x| x };\n",
+            after: "a.do { |x| x };\n",
+        }
+    }
+
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
         let src = cx.bytes();
         let bytes: &[u8] = &src;
         let len = bytes.len();
 
         let mut edits = Vec::new();
-
-        let mut in_line_comment = false;
-        let mut in_block_comment = false;
-        let mut in_single_str = false;
-        let mut in_double_str = false;
+        let classes = cx.classes();
 
         let mut i = 0usize;
         while i < len {
             let b = bytes[i];
 
-            // inside comments/strings
-            if in_line_comment {
-                if b == b'\n' {
-                    in_line_comment = false;
-                }
-                i += 1;
-                continue;
-            }
-
-            if in_block_comment {
-                if b == b'*' && i + 1 < len && bytes[i + 1] == b'/' {
-                    in_block_comment = false;
-                    i += 2;
-                } else {
-                    i += 1;
-                }
-                continue;
-            }
-
-            if in_single_str {
-                if b == b'\'' && !is_escaped(bytes, i) {
-                    in_single_str = false;
-                }
-                i += 1;
-                continue;
-            }
-
-            if in_double_str {
-                if b == b'"' && !is_escaped(bytes, i) {
-                    in_double_str = false;
-                }
-                i += 1;
-                continue;
-            }
-
-            // entering comments/strings
-            if b == b'/' && i + 1 < len {
-                if bytes[i + 1] == b'/' {
-                    in_line_comment = true;
-                    i += 2;
-                    continue;
-                } else if bytes[i + 1] == b'*' {
-                    in_block_comment = true;
-                    i += 2;
-                    continue;
-                }
-            }
-
-            if b == b'\'' {
-                in_single_str = true;
-                i += 1;
-                continue;
-            }
-
-            if b == b'"' {
-                in_double_str = true;
+            // Leave strings, symbols and comments alone.
+            if !classes.is_code(i) {
                 i += 1;
                 continue;
             }
@@ -1,5 +1,5 @@
 use crate::engine::{Ctx, TextEdit};
-use crate::rules::Rule;
+use crate::rules::{Category, Example, Rule};
 use anyhow::Result;
 
 const fn is_space(b: u8) -> bool {
@@ -266,6 +266,21 @@ impl Rule for BlockLayoutKAndR {
         "block_layout_kandr"
     }
 
+    fn description(&self) -> &'static str {
+        "Put opening braces on the line of their `if`/`else` header (K&R style)."
+    }
+
+    fn category(&self) -> Category {
+        Category::Structural
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "if (x > 0)\n{\n    1\n}\nelse\n{\n    2\n};\n",
+            after: "if (x > 0) {\n    1\n} else {\n    2\n};\n",
+        }
+    }
+
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
         // 1) Attach `{` to "header" lines (x =, if (...), etc.)
         let n1 = self.attach_open_braces(cx)?;
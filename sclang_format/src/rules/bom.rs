@@ -0,0 +1,40 @@
+use crate::engine::{Ctx, TextEdit};
+use crate::rules::{Category, Example, Rule};
+use anyhow::Result;
+
+const BOM: &str = "\u{feff}";
+
+pub struct StripByteOrderMark;
+
+impl Rule for StripByteOrderMark {
+    fn name(&self) -> &'static str {
+        "byte_order_mark"
+    }
+
+    fn description(&self) -> &'static str {
+        "Drop a UTF-8 byte order mark at the start of the file."
+    }
+
+    fn category(&self) -> Category {
+        Category::Normalize
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "\u{feff}x = 1;\n",
+            after: "x = 1;\n",
+        }
+    }
+
+    fn run(&self, cx: &mut Ctx) -> Result<usize> {
+        if !cx.bytes().starts_with(BOM.as_bytes()) {
+            return Ok(0);
+        }
+        cx.apply_edits(vec![TextEdit {
+            start_byte: 0,
+            end_byte: BOM.len(),
+            replacement: String::new(),
+        }])?;
+        Ok(1)
+    }
+}
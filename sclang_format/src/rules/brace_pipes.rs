@@ -1,7 +1,8 @@
 use anyhow::Result;
-use tree_sitter::{Node, Query, QueryCursor, StreamingIterator};
+use tree_sitter::{Query, QueryCursor, StreamingIterator};
 
 use crate::engine::{Ctx, TextEdit};
+use crate::rules::{Category, Example};
 
 pub struct BraceAndPipesSingleLine;
 
@@ -9,19 +10,6 @@ impl BraceAndPipesSingleLine {
     fn same_line(src: &[u8], a: usize, b: usize) -> bool {
         !src[a..b].contains(&b'\n')
     }
-
-    #[inline]
-    fn in_comment_or_string(root: Node, byte: usize) -> bool {
-        let mut cur = root.descendant_for_byte_range(byte, byte + 1);
-        while let Some(n) = cur {
-            match n.kind() {
-                "comment" | "block_comment" | "line_comment" | "string" => return true,
-                _ => {}
-            }
-            cur = n.parent();
-        }
-        false
-    }
 }
 
 impl crate::rules::Rule for BraceAndPipesSingleLine {
@@ -29,10 +17,26 @@ impl crate::rules::Rule for BraceAndPipesSingleLine {
         "brace_pipe_spacing"
     }
 
+    fn description(&self) -> &'static str {
+        "Pad the inside of single-line function braces with one space."
+    }
+
+    fn category(&self) -> Category {
+        Category::Spacing
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "f = {1 + 2};\n",
+            after: "f = { 1 + 2 };\n",
+        }
+    }
+
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
         let bytes = cx.bytes();
         let src = bytes.as_slice();
         let root = cx.tree.root_node();
+        let classes = cx.classes();
         let lang = cx.tree.language();
 
         let mut edits = Vec::<TextEdit>::new();
@@ -54,7 +58,7 @@ impl crate::rules::Rule for BraceAndPipesSingleLine {
             if !Self::same_line(src, open, close) {
                 continue;
             }
-            if Self::in_comment_or_string(root, open) {
+            if !classes.is_code(open) {
                 continue;
             }
 
@@ -1,7 +1,7 @@
 // src/rules/call_index_paren.rs
 
 use crate::engine::{Ctx, TextEdit};
-use crate::rules::Rule;
+use crate::rules::{Category, Example, Rule};
 use anyhow::Result;
 
 const fn is_space(b: u8) -> bool {
@@ -16,21 +16,6 @@ const fn is_ident_char(b: u8) -> bool {
     b.is_ascii_lowercase() || b.is_ascii_uppercase() || b.is_ascii_digit() || b == b'_'
 }
 
-/// Check if the quote at `i` is escaped by an odd number of backslashes.
-fn is_escaped(bytes: &[u8], i: usize) -> bool {
-    let mut count = 0;
-    let mut j = i;
-    while j > 0 {
-        j -= 1;
-        if bytes[j] == b'\\' {
-            count += 1;
-        } else {
-            break;
-        }
-    }
-    count % 2 == 1
-}
-
 fn is_keyword(bytes: &[u8], start: usize, end: usize) -> bool {
     let word = &bytes[start..end];
     word == b"if" || word == b"while" || word == b"for" || word == b"switch" || word == b"case"
@@ -103,78 +88,35 @@ impl Rule for CallIndexParenSpacing {
         "call_index_paren_spacing"
     }
 
+    fn description(&self) -> &'static str {
+        "No space between a callee and `(` or a receiver and `[`."
+    }
+
+    fn category(&self) -> Category {
+        Category::Spacing
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "f (1); a [0];\n",
+            after: "f(1); a[0];\n",
+        }
+    }
+
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
         let src = cx.bytes();
         let bytes: &[u8] = &src;
         let len = bytes.len();
 
         let mut edits = Vec::new();
-
-        let mut in_line_comment = false;
-        let mut in_block_comment = false;
-        let mut in_single_str = false;
-        let mut in_double_str = false;
+        let classes = cx.classes();
 
         let mut i = 0usize;
         while i < len {
             let b = bytes[i];
 
-            // inside comments/strings
-            if in_line_comment {
-                if b == b'\n' {
-                    in_line_comment = false;
-                }
-                i += 1;
-                continue;
-            }
-
-            if in_block_comment {
-                if b == b'*' && i + 1 < len && bytes[i + 1] == b'/' {
-                    in_block_comment = false;
-                    i += 2;
-                } else {
-                    i += 1;
-                }
-                continue;
-            }
-
-            if in_single_str {
-                if b == b'\'' && !is_escaped(bytes, i) {
-                    in_single_str = false;
-                }
-                i += 1;
-                continue;
-            }
-
-            if in_double_str {
-                if b == b'"' && !is_escaped(bytes, i) {
-                    in_double_str = false;
-                }
-                i += 1;
-                continue;
-            }
-
-            // entering comments/strings
-            if b == b'/' && i + 1 < len {
-                if bytes[i + 1] == b'/' {
-                    in_line_comment = true;
-                    i += 2;
-                    continue;
-                } else if bytes[i + 1] == b'*' {
-                    in_block_comment = true;
-                    i += 2;
-                    continue;
-                }
-            }
-
-            if b == b'\'' {
-                in_single_str = true;
-                i += 1;
-                continue;
-            }
-
-            if b == b'"' {
-                in_double_str = true;
+            // Leave strings, symbols and comments alone.
+            if !classes.is_code(i) {
                 i += 1;
                 continue;
             }
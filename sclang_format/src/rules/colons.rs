@@ -1,28 +1,13 @@
 // src/rules/colons.rs
 
 use anyhow::Result;
-use tree_sitter::Node;
 
 use crate::engine::{Ctx, TextEdit};
-use crate::rules::Rule;
+use crate::rules::{Category, Example, Rule};
 
 pub struct AddSpacesAroundColons;
 
 impl AddSpacesAroundColons {
-    #[inline]
-    fn in_comment_or_string(root: Node, byte: usize) -> bool {
-        let mut cur = root.descendant_for_byte_range(byte, byte + 1);
-        while let Some(n) = cur {
-            match n.kind() {
-                // conservative skip list; adjust if your grammar uses different names
-                "comment" | "block_comment" | "line_comment" | "string" => return true,
-                _ => {}
-            }
-            cur = n.parent();
-        }
-        false
-    }
-
     #[inline]
     fn fix_one(bytes: &[u8], colon: usize, edits: &mut Vec<TextEdit>) {
         let len = bytes.len();
@@ -93,8 +78,23 @@ impl Rule for AddSpacesAroundColons {
         "spaces_around_colons"
     }
 
+    fn description(&self) -> &'static str {
+        "No space before a key's `:` and one space after it."
+    }
+
+    fn category(&self) -> Category {
+        Category::Spacing
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "e = (a:1, b :2);\n",
+            after: "e = (a: 1, b: 2);\n",
+        }
+    }
+
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
-        let root = cx.tree.root_node();
+        let classes = cx.classes();
         let src = cx.bytes();
         let bytes: &[u8] = &src;
 
@@ -105,7 +105,7 @@ impl Rule for AddSpacesAroundColons {
                 continue;
             }
 
-            if Self::in_comment_or_string(root, i) {
+            if !classes.is_code(i) {
                 continue;
             }
 
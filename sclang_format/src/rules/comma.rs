@@ -1,5 +1,5 @@
 use crate::engine::{Ctx, TextEdit};
-use crate::rules::Rule;
+use crate::rules::{Category, Example, Rule};
 use tree_sitter::StreamingIterator;
 use tree_sitter::{Query, QueryCursor};
 
@@ -9,7 +9,22 @@ pub struct AddSpacesAfterCommas;
 
 impl Rule for AddSpacesAfterCommas {
     fn name(&self) -> &'static str {
-        "AddSpacesAfterCommas"
+        "spaces_after_commas"
+    }
+
+    fn description(&self) -> &'static str {
+        "No space before a comma and one space after it."
+    }
+
+    fn category(&self) -> Category {
+        Category::Spacing
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "f(1,2 ,3);\n",
+            after: "f(1, 2, 3);\n",
+        }
     }
 
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
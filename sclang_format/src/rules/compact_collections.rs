@@ -1,9 +1,7 @@
-use crate::engine::{Ctx, TextEdit};
-use crate::rules::Rule;
+use crate::engine::{ClassMap, Ctx, TextEdit};
+use crate::rules::{Category, Example, Rule};
 use anyhow::Result;
 
-const MAX_LINE_WIDTH: usize = 80;
-
 const fn is_space(b: u8) -> bool {
     b == b' ' || b == b'\t'
 }
@@ -12,82 +10,6 @@ const fn is_newline(b: u8) -> bool {
     b == b'\n' || b == b'\r'
 }
 
-#[derive(Clone, Copy, Debug)]
-struct CommentState {
-    in_line: bool,
-    in_block: bool,
-    in_single: bool,
-    in_double: bool,
-    prev: u8,
-}
-
-impl CommentState {
-    const fn new() -> Self {
-        CommentState {
-            in_line: false,
-            in_block: false,
-            in_single: false,
-            in_double: false,
-            prev: 0,
-        }
-    }
-
-    fn in_comment_or_string(&self) -> bool {
-        self.in_line || self.in_block || self.in_single || self.in_double
-    }
-
-    fn step(&mut self, b: u8) {
-        // line comments
-        if self.in_line {
-            if is_newline(b) {
-                self.in_line = false;
-            }
-            self.prev = b;
-            return;
-        }
-
-        // block comments
-        if self.in_block {
-            if self.prev == b'*' && b == b'/' {
-                self.in_block = false;
-            }
-            self.prev = b;
-            return;
-        }
-
-        // single-quoted string
-        if self.in_single {
-            if b == b'\'' && self.prev != b'\\' {
-                self.in_single = false;
-            }
-            self.prev = b;
-            return;
-        }
-
-        // double-quoted string
-        if self.in_double {
-            if b == b'"' && self.prev != b'\\' {
-                self.in_double = false;
-            }
-            self.prev = b;
-            return;
-        }
-
-        // not inside anything yet
-        if self.prev == b'/' && b == b'/' {
-            self.in_line = true;
-        } else if self.prev == b'/' && b == b'*' {
-            self.in_block = true;
-        } else if b == b'\'' {
-            self.in_single = true;
-        } else if b == b'"' {
-            self.in_double = true;
-        }
-
-        self.prev = b;
-    }
-}
-
 fn slice_has_newline(bytes: &[u8], start: usize, end: usize) -> bool {
     let mut i = start;
     while i < end {
@@ -99,33 +21,21 @@ fn slice_has_newline(bytes: &[u8], start: usize, end: usize) -> bool {
     false
 }
 
-fn slice_has_comment(bytes: &[u8], start: usize, end: usize) -> bool {
-    let mut cs = CommentState::new();
-    let mut i = start;
-    while i < end {
-        let b = bytes[i];
-        cs.step(b);
-        // consider either // or /* as enough to bail
-        if cs.in_line || cs.in_block {
-            return true;
-        }
-        i += 1;
-    }
-    false
-}
-
 /// match matching delimiter (e.g. '[' → ']'), ignoring anything inside comments/strings.
-fn match_delim(bytes: &[u8], open: usize, open_b: u8, close_b: u8) -> Option<usize> {
+fn match_delim(
+    bytes: &[u8],
+    classes: &ClassMap,
+    open: usize,
+    open_b: u8,
+    close_b: u8,
+) -> Option<usize> {
     let len = bytes.len();
     let mut depth = 0usize;
-    let mut cs = CommentState::new();
 
     let mut i = open;
     while i < len {
         let b = bytes[i];
-        cs.step(b);
-
-        if cs.in_comment_or_string() {
+        if !classes.is_code(i) {
             i += 1;
             continue;
         }
@@ -150,8 +60,7 @@ fn match_delim(bytes: &[u8], open: usize, open_b: u8, close_b: u8) -> Option<usi
 }
 
 /// True if there is a top-level `:` inside `( ... )`, treating that as an event.
-fn has_top_level_event_colon(bytes: &[u8], open: usize, close: usize) -> bool {
-    let mut cs = CommentState::new();
+fn has_top_level_event_colon(bytes: &[u8], classes: &ClassMap, open: usize, close: usize) -> bool {
     let mut par_depth = 0i32;
     let mut square_depth = 0i32;
     let mut brace_depth = 0i32;
@@ -159,9 +68,7 @@ fn has_top_level_event_colon(bytes: &[u8], open: usize, close: usize) -> bool {
     let mut i = open;
     while i <= close {
         let b = bytes[i];
-        cs.step(b);
-
-        if cs.in_comment_or_string() {
+        if !classes.is_code(i) {
             i += 1;
             continue;
         }
@@ -189,10 +96,10 @@ fn has_top_level_event_colon(bytes: &[u8], open: usize, close: usize) -> bool {
 
 fn collect_top_level_elements(
     bytes: &[u8],
+    classes: &ClassMap,
     inner_start: usize,
     inner_end: usize,
 ) -> Vec<(usize, usize)> {
-    let mut cs = CommentState::new();
     let mut par_depth = 0i32;
     let mut square_depth = 0i32;
     let mut brace_depth = 0i32;
@@ -203,9 +110,7 @@ fn collect_top_level_elements(
     let mut i = inner_start;
     while i < inner_end {
         let b = bytes[i];
-        cs.step(b);
-
-        if cs.in_comment_or_string() {
+        if !classes.is_code(i) {
             i += 1;
             continue;
         }
@@ -256,19 +161,26 @@ fn trim_ascii_slice(s: &str) -> &str {
 pub struct CompactShortCollections;
 
 impl CompactShortCollections {
-    fn try_compact_array(bytes: &[u8], open: usize, close: usize, edits: &mut Vec<TextEdit>) {
+    fn try_compact_array(
+        bytes: &[u8],
+        classes: &ClassMap,
+        open: usize,
+        close: usize,
+        max_width: usize,
+        edits: &mut Vec<TextEdit>,
+    ) {
         // Must be multi-line
         if !slice_has_newline(bytes, open + 1, close) {
             return;
         }
 
         // Skip arrays that contain comments
-        if slice_has_comment(bytes, open + 1, close) {
+        if classes.has_comment(open + 1..close) {
             return;
         }
 
         // Collect top-level elements
-        let elems = collect_top_level_elements(bytes, open + 1, close);
+        let elems = collect_top_level_elements(bytes, classes, open + 1, close);
         let mut trimmed_parts = Vec::new();
 
         for (s, e) in elems {
@@ -307,7 +219,7 @@ impl CompactShortCollections {
         let old_line_len = line_end - line_start;
         let new_line_len = old_line_len - old_inner_len + replacement.len();
 
-        if new_line_len <= MAX_LINE_WIDTH {
+        if new_line_len <= max_width {
             edits.push(TextEdit {
                 start_byte: open,
                 end_byte: close + 1,
@@ -316,24 +228,31 @@ impl CompactShortCollections {
         }
     }
 
-    fn try_compact_event(bytes: &[u8], open: usize, close: usize, edits: &mut Vec<TextEdit>) {
+    fn try_compact_event(
+        bytes: &[u8],
+        classes: &ClassMap,
+        open: usize,
+        close: usize,
+        max_width: usize,
+        edits: &mut Vec<TextEdit>,
+    ) {
         // Must be multi-line
         if !slice_has_newline(bytes, open + 1, close) {
             return;
         }
 
         // Skip events that contain comments
-        if slice_has_comment(bytes, open + 1, close) {
+        if classes.has_comment(open + 1..close) {
             return;
         }
 
         // Only treat things that *really* look like events
-        if !has_top_level_event_colon(bytes, open, close) {
+        if !has_top_level_event_colon(bytes, classes, open, close) {
             return;
         }
 
         // Collect top-level comma-separated "key: value" entries
-        let elems = collect_top_level_elements(bytes, open + 1, close);
+        let elems = collect_top_level_elements(bytes, classes, open + 1, close);
         let mut trimmed_parts = Vec::new();
 
         for (s, e) in elems {
@@ -370,7 +289,7 @@ impl CompactShortCollections {
         let old_line_len = line_end - line_start;
         let new_line_len = old_line_len - old_inner_len + replacement.len();
 
-        if new_line_len <= MAX_LINE_WIDTH {
+        if new_line_len <= max_width {
             edits.push(TextEdit {
                 start_byte: open,
                 end_byte: close + 1,
@@ -385,34 +304,48 @@ impl Rule for CompactShortCollections {
         "compact_short_collections"
     }
 
+    fn description(&self) -> &'static str {
+        "Join a multi-line array or event onto one line when it fits."
+    }
+
+    fn category(&self) -> Category {
+        Category::WidthAware
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "a = [\n    1,\n    2\n];\n",
+            after: "a = [1, 2];\n",
+        }
+    }
+
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
         let src = cx.bytes();
         let bytes: &[u8] = src.as_slice();
         let len = bytes.len();
+        let max_width = cx.max_width;
+        let classes = cx.classes();
 
         let mut edits = Vec::new();
-        let mut cs = CommentState::new();
         let mut i = 0;
 
         while i < len {
             let b = bytes[i];
-            cs.step(b);
-
-            if cs.in_comment_or_string() {
+            if !classes.is_code(i) {
                 i += 1;
                 continue;
             }
 
             if b == b'[' {
-                if let Some(close) = match_delim(bytes, i, b'[', b']') {
-                    Self::try_compact_array(bytes, i, close, &mut edits);
+                if let Some(close) = match_delim(bytes, classes, i, b'[', b']') {
+                    Self::try_compact_array(bytes, classes, i, close, max_width, &mut edits);
                     // skip past this array; we won't touch its interior again in this pass
                     i = close + 1;
                     continue;
                 }
             } else if b == b'(' {
-                if let Some(close) = match_delim(bytes, i, b'(', b')') {
-                    Self::try_compact_event(bytes, i, close, &mut edits);
+                if let Some(close) = match_delim(bytes, classes, i, b'(', b')') {
+                    Self::try_compact_event(bytes, classes, i, close, max_width, &mut edits);
                     i = close + 1;
                     continue;
                 }
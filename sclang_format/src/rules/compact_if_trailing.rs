@@ -1,9 +1,7 @@
-use crate::engine::{Ctx, TextEdit};
-use crate::rules::Rule;
+use crate::engine::{ClassMap, Ctx, TextEdit};
+use crate::rules::{Category, Example, Rule};
 use anyhow::Result;
 
-const MAX_LINE_WIDTH: usize = 80;
-
 const fn is_space(b: u8) -> bool {
     b == b' ' || b == b'\t'
 }
@@ -19,88 +17,14 @@ fn is_ident_char(b: u8) -> bool {
         || b == b'_'
 }
 
-#[derive(Clone, Copy, Debug)]
-struct CommentState {
-    in_line: bool,
-    in_block: bool,
-    in_single: bool,
-    in_double: bool,
-    prev: u8,
-}
-
-impl CommentState {
-    const fn new() -> Self {
-        CommentState {
-            in_line: false,
-            in_block: false,
-            in_single: false,
-            in_double: false,
-            prev: 0,
-        }
-    }
-
-    fn in_comment_or_string(&self) -> bool {
-        self.in_line || self.in_block || self.in_single || self.in_double
-    }
-
-    fn step(&mut self, b: u8) {
-        if self.in_line {
-            if is_newline(b) {
-                self.in_line = false;
-            }
-            self.prev = b;
-            return;
-        }
-
-        if self.in_block {
-            if self.prev == b'*' && b == b'/' {
-                self.in_block = false;
-            }
-            self.prev = b;
-            return;
-        }
-
-        if self.in_single {
-            if b == b'\'' && self.prev != b'\\' {
-                self.in_single = false;
-            }
-            self.prev = b;
-            return;
-        }
-
-        if self.in_double {
-            if b == b'"' && self.prev != b'\\' {
-                self.in_double = false;
-            }
-            self.prev = b;
-            return;
-        }
-
-        if self.prev == b'/' && b == b'/' {
-            self.in_line = true;
-        } else if self.prev == b'/' && b == b'*' {
-            self.in_block = true;
-        } else if b == b'\'' {
-            self.in_single = true;
-        } else if b == b'"' {
-            self.in_double = true;
-        }
-
-        self.prev = b;
-    }
-}
-
-fn match_paren(bytes: &[u8], open: usize) -> Option<usize> {
+fn match_paren(bytes: &[u8], classes: &ClassMap, open: usize) -> Option<usize> {
     let len = bytes.len();
     let mut depth = 0usize;
-    let mut cs = CommentState::new();
 
     let mut i = open;
     while i < len {
         let b = bytes[i];
-        cs.step(b);
-
-        if cs.in_comment_or_string() {
+        if !classes.is_code(i) {
             i += 1;
             continue;
         }
@@ -122,17 +46,14 @@ fn match_paren(bytes: &[u8], open: usize) -> Option<usize> {
     None
 }
 
-fn match_brace(bytes: &[u8], open: usize) -> Option<usize> {
+fn match_brace(bytes: &[u8], classes: &ClassMap, open: usize) -> Option<usize> {
     let len = bytes.len();
     let mut depth = 0usize;
-    let mut cs = CommentState::new();
 
     let mut i = open;
     while i < len {
         let b = bytes[i];
-        cs.step(b);
-
-        if cs.in_comment_or_string() {
+        if !classes.is_code(i) {
             i += 1;
             continue;
         }
@@ -172,7 +93,12 @@ fn trim_ascii_slice(s: &str) -> &str {
 pub struct CompactShortIfTrailingClosures;
 
 impl CompactShortIfTrailingClosures {
-    fn try_compact_at(bytes: &[u8], start: usize) -> Option<TextEdit> {
+    fn try_compact_at(
+        bytes: &[u8],
+        classes: &ClassMap,
+        start: usize,
+        max_width: usize,
+    ) -> Option<TextEdit> {
         let len = bytes.len();
 
         // "if" token
@@ -203,7 +129,7 @@ impl CompactShortIfTrailingClosures {
             return None;
         }
         let cond_open = i;
-        let cond_close = match match_paren(bytes, cond_open) {
+        let cond_close = match match_paren(bytes, classes, cond_open) {
             Some(c) => c,
             None => return None,
         };
@@ -222,7 +148,7 @@ impl CompactShortIfTrailingClosures {
             return None;
         }
         let block1_open = i;
-        let block1_close = match match_brace(bytes, block1_open) {
+        let block1_close = match match_brace(bytes, classes, block1_open) {
             Some(c) => c,
             None => return None,
         };
@@ -241,7 +167,7 @@ impl CompactShortIfTrailingClosures {
             return None;
         }
         let block2_open = i;
-        let block2_close = match match_brace(bytes, block2_open) {
+        let block2_close = match match_brace(bytes, classes, block2_open) {
             Some(c) => c,
             None => return None,
         };
@@ -303,6 +229,7 @@ impl CompactShortIfTrailingClosures {
         replacement.push('{');
         replacement.push(' ');
         replacement.push_str(b2_body);
+        replacement.push(' ');
         replacement.push('}');
         if has_semi {
             replacement.push(';');
@@ -313,7 +240,7 @@ impl CompactShortIfTrailingClosures {
         let old_line_len = line_end - line_start;
         let new_line_len = old_line_len - old_segment_len + replacement.len();
 
-        if new_line_len > MAX_LINE_WIDTH {
+        if new_line_len > max_width {
             return None;
         }
 
@@ -330,26 +257,40 @@ impl Rule for CompactShortIfTrailingClosures {
         "compact_short_if_trailing_closures"
     }
 
+    fn description(&self) -> &'static str {
+        "Join a multi-line `if` with short single-line branches onto one line when it fits."
+    }
+
+    fn category(&self) -> Category {
+        Category::WidthAware
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "if (x) { 1 }\n{ 2 };\n",
+            after: "if (x) { 1 } { 2 };\n",
+        }
+    }
+
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
         let src = cx.bytes();
         let bytes: &[u8] = src.as_slice();
         let len = bytes.len();
+        let max_width = cx.max_width;
+        let classes = cx.classes();
 
         let mut edits = Vec::new();
-        let mut cs = CommentState::new();
         let mut i = 0;
 
         while i < len {
             let b = bytes[i];
-            cs.step(b);
-
-            if cs.in_comment_or_string() {
+            if !classes.is_code(i) {
                 i += 1;
                 continue;
             }
 
             if b == b'i' && i + 1 < len && bytes[i + 1] == b'f' {
-                if let Some(edit) = Self::try_compact_at(bytes, i) {
+                if let Some(edit) = Self::try_compact_at(bytes, classes, i, max_width) {
                     // skip over replaced segment; we only generate one edit per `if`
                     let skip_to = edit.end_byte;
                     edits.push(edit);
@@ -1,5 +1,5 @@
 use crate::engine::{Ctx, TextEdit};
-use crate::rules::Rule;
+use crate::rules::{Category, Example, Rule};
 use anyhow::Result;
 
 const fn is_space(byte: u8) -> bool {
@@ -21,72 +21,32 @@ impl Rule for DotChainLayout {
         "dot_chain_layout"
     }
 
+    fn description(&self) -> &'static str {
+        "Start each continued line of a method chain with its `.`."
+    }
+
+    fn category(&self) -> Category {
+        Category::Structural
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "x = Pbind().\n    play;\n",
+            after: "x = Pbind()\n    .play;\n",
+        }
+    }
+
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
         let bytes = cx.bytes();
         let len = bytes.len();
+        let classes = cx.classes();
         let mut edits = Vec::new();
 
-        // very simple comment/string tracking
-        let mut in_line_comment = false;
-        let mut in_block_comment = false;
-        let mut in_single_str = false;
-        let mut in_double_str = false;
-
         let mut i = 0usize;
         while i < len {
             let byte = bytes[i];
 
-            // handle being inside comment/string
-            if in_line_comment {
-                if byte == b'\n' {
-                    in_line_comment = false;
-                }
-                i += 1;
-                continue;
-            }
-            if in_block_comment {
-                if byte == b'*' && i + 1 < len && bytes[i + 1] == b'/' {
-                    in_block_comment = false;
-                    i += 2;
-                } else {
-                    i += 1;
-                }
-                continue;
-            }
-            if in_single_str {
-                if byte == b'\'' {
-                    in_single_str = false;
-                }
-                i += 1;
-                continue;
-            }
-            if in_double_str {
-                if byte == b'"' {
-                    in_double_str = false;
-                }
-                i += 1;
-                continue;
-            }
-
-            // entering comment / string
-            if byte == b'/' && i + 1 < len {
-                if bytes[i + 1] == b'/' {
-                    in_line_comment = true;
-                    i += 2;
-                    continue;
-                } else if bytes[i + 1] == b'*' {
-                    in_block_comment = true;
-                    i += 2;
-                    continue;
-                }
-            }
-            if byte == b'\'' {
-                in_single_str = true;
-                i += 1;
-                continue;
-            }
-            if byte == b'"' {
-                in_double_str = true;
+            if !classes.is_code(i) {
                 i += 1;
                 continue;
             }
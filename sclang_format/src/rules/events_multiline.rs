@@ -1,5 +1,5 @@
-use crate::engine::{Ctx, TextEdit};
-use crate::rules::Rule;
+use crate::engine::{ClassMap, Ctx, TextEdit};
+use crate::rules::{Category, Example, Rule};
 use anyhow::Result;
 
 const fn is_space(b: u8) -> bool {
@@ -10,89 +10,14 @@ const fn is_newline(b: u8) -> bool {
     b == b'\n' || b == b'\r'
 }
 
-#[derive(Clone, Copy, Debug)]
-struct CommentState {
-    in_line: bool,
-    in_block: bool,
-    in_single: bool,
-    in_double: bool,
-    prev: u8,
-}
-
-impl CommentState {
-    const fn new() -> Self {
-        Self {
-            in_line: false,
-            in_block: false,
-            in_single: false,
-            in_double: false,
-            prev: 0,
-        }
-    }
-
-    const fn in_comment_or_string(&self) -> bool {
-        self.in_line || self.in_block || self.in_single || self.in_double
-    }
-
-    const fn step(&mut self, b: u8) {
-        // already inside something
-        if self.in_line {
-            if b == b'\n' {
-                self.in_line = false;
-            }
-            self.prev = b;
-            return;
-        }
-
-        if self.in_block {
-            if self.prev == b'*' && b == b'/' {
-                self.in_block = false;
-            }
-            self.prev = b;
-            return;
-        }
-
-        if self.in_single {
-            if b == b'\'' && self.prev != b'\\' {
-                self.in_single = false;
-            }
-            self.prev = b;
-            return;
-        }
-
-        if self.in_double {
-            if b == b'"' && self.prev != b'\\' {
-                self.in_double = false;
-            }
-            self.prev = b;
-            return;
-        }
-
-        // not inside anything yet
-        if self.prev == b'/' && b == b'/' {
-            self.in_line = true;
-        } else if self.prev == b'/' && b == b'*' {
-            self.in_block = true;
-        } else if b == b'\'' {
-            self.in_single = true;
-        } else if b == b'"' {
-            self.in_double = true;
-        }
-
-        self.prev = b;
-    }
-}
-
 /// Find the matching ')' for the '(' at `open`.
-fn match_paren(bytes: &[u8], open: usize) -> Option<usize> {
+fn match_paren(bytes: &[u8], classes: &ClassMap, open: usize) -> Option<usize> {
     let len = bytes.len();
     let mut depth: isize = 1;
-    let mut cs = CommentState::new();
 
     for i in open + 1..len {
         let b = bytes[i];
-        cs.step(b);
-        if cs.in_comment_or_string() {
+        if !classes.is_code(i) {
             continue;
         }
 
@@ -114,16 +39,14 @@ fn is_multiline(bytes: &[u8], open: usize, close: usize) -> bool {
 }
 
 /// Does this `( ... )` look like an event literal, i.e. has a top-level `:`?
-fn has_top_level_event_colon(bytes: &[u8], open: usize, close: usize) -> bool {
-    let mut cs = CommentState::new();
+fn has_top_level_event_colon(bytes: &[u8], classes: &ClassMap, open: usize, close: usize) -> bool {
     let mut par: isize = 1;
     let mut br: isize = 0;
     let mut brk: isize = 0;
 
     for i in open + 1..close {
         let b = bytes[i];
-        cs.step(b);
-        if cs.in_comment_or_string() {
+        if !classes.is_code(i) {
             continue;
         }
 
@@ -146,8 +69,13 @@ fn has_top_level_event_colon(bytes: &[u8], open: usize, close: usize) -> bool {
 
 /// Decide whether the comma at `comma_idx` is followed by another
 /// top-level `key: value` pair **on the same logical line** inside this event.
-fn comma_starts_event_key(bytes: &[u8], comma_idx: usize, _open: usize, close: usize) -> bool {
-    let mut cs = CommentState::new();
+fn comma_starts_event_key(
+    bytes: &[u8],
+    classes: &ClassMap,
+    comma_idx: usize,
+    _open: usize,
+    close: usize,
+) -> bool {
     let mut par: isize = 1;
     let mut br: isize = 0;
     let mut brk: isize = 0;
@@ -175,8 +103,7 @@ fn comma_starts_event_key(bytes: &[u8], comma_idx: usize, _open: usize, close: u
             return false;
         }
 
-        cs.step(c);
-        if cs.in_comment_or_string() {
+        if !classes.is_code(k) {
             continue;
         }
 
@@ -216,8 +143,13 @@ fn comma_starts_event_key(bytes: &[u8], comma_idx: usize, _open: usize, close: u
 
 /// Split an event like `(freq: 440, amp: 0.1, pan: -0.5)` into one key per line,
 /// preserving indentation and avoiding comments/strings.
-fn split_event_items(bytes: &[u8], open: usize, close: usize, edits: &mut Vec<TextEdit>) {
-    let mut cs = CommentState::new();
+fn split_event_items(
+    bytes: &[u8],
+    classes: &ClassMap,
+    open: usize,
+    close: usize,
+    edits: &mut Vec<TextEdit>,
+) {
     let mut par: isize = 1;
     let mut br: isize = 0;
     let mut brk: isize = 0;
@@ -226,8 +158,7 @@ fn split_event_items(bytes: &[u8], open: usize, close: usize, edits: &mut Vec<Te
     // 1) collect top-level commas inside this `( ... )`
     for i in open + 1..close {
         let b = bytes[i];
-        cs.step(b);
-        if cs.in_comment_or_string() {
+        if !classes.is_code(i) {
             continue;
         }
 
@@ -250,7 +181,6 @@ fn split_event_items(bytes: &[u8], open: usize, close: usize, edits: &mut Vec<Te
     }
 
     // 2) determine indentation based on the first key line
-    let mut cs2 = CommentState::new();
     par = 1;
     br = 0;
     brk = 0;
@@ -258,8 +188,7 @@ fn split_event_items(bytes: &[u8], open: usize, close: usize, edits: &mut Vec<Te
 
     'outer: for i in open + 1..close {
         let b = bytes[i];
-        cs2.step(b);
-        if cs2.in_comment_or_string() {
+        if !classes.is_code(i) {
             continue;
         }
 
@@ -300,7 +229,7 @@ fn split_event_items(bytes: &[u8], open: usize, close: usize, edits: &mut Vec<Te
         }
 
         // Only touch commas that clearly start another event key.
-        if !comma_starts_event_key(bytes, comma_idx, open, close) {
+        if !comma_starts_event_key(bytes, classes, comma_idx, open, close) {
             continue;
         }
 
@@ -331,30 +260,44 @@ impl Rule for MultiLineEventsOnePerLine {
         "multi_line_events_one_per_line"
     }
 
+    fn description(&self) -> &'static str {
+        "Put each key of a multi-line event on its own line."
+    }
+
+    fn category(&self) -> Category {
+        Category::Structural
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "e = (\n    a: 1, b: 2\n);\n",
+            after: "e = (\n    a: 1,\n    b: 2\n);\n",
+        }
+    }
+
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
         let src = cx.bytes();
         let bytes: &[u8] = &src;
         let len = bytes.len();
+        let classes = cx.classes();
 
         let mut edits: Vec<TextEdit> = Vec::new();
-        let mut cs = CommentState::new();
 
         let mut i = 0usize;
         while i < len {
             let b = bytes[i];
-            cs.step(b);
 
             // Ignore anything inside comments/strings entirely.
-            if cs.in_comment_or_string() {
+            if !classes.is_code(i) {
                 i += 1;
                 continue;
             }
 
             if b == b'('
-                && let Some(close) = match_paren(bytes, i) {
+                && let Some(close) = match_paren(bytes, classes, i) {
                     // Only bother with genuinely multi-line parens that look like events.
-                    if is_multiline(bytes, i, close) && has_top_level_event_colon(bytes, i, close) {
-                        split_event_items(bytes, i, close, &mut edits);
+                    if is_multiline(bytes, i, close) && has_top_level_event_colon(bytes, classes, i, close) {
+                        split_event_items(bytes, classes, i, close, &mut edits);
                     }
                 }
 
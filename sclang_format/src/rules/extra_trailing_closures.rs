@@ -1,87 +1,23 @@
 // src/rules/extra_trailing_closures.rs
 
-use crate::engine::{Ctx, TextEdit};
-use crate::rules::Rule;
+use crate::engine::{ClassMap, Ctx, TextEdit};
+use crate::rules::{Category, Example, Rule};
 use anyhow::Result;
 
 const fn is_ident_char(b: u8) -> bool {
     b.is_ascii_alphanumeric() || b == b'_'
 }
 
-#[derive(Default)]
-struct ScanState {
-    in_line_comment: bool,
-    in_block_comment: bool,
-    in_single_str: bool,
-    in_double_str: bool,
-}
-
-impl ScanState {
-    fn step(&mut self, bytes: &[u8], i: usize) {
-        let b = bytes[i];
-
-        if self.in_line_comment {
-            if b == b'\n' {
-                self.in_line_comment = false;
-            }
-            return;
-        }
-
-        if self.in_block_comment {
-            if b == b'*' && i + 1 < bytes.len() && bytes[i + 1] == b'/' {
-                self.in_block_comment = false;
-            }
-            return;
-        }
-
-        if self.in_single_str {
-            if b == b'\\' && i + 1 < bytes.len() {
-                // skip escaped char
-            } else if b == b'\'' {
-                self.in_single_str = false;
-            }
-            return;
-        }
-
-        if self.in_double_str {
-            if b == b'\\' && i + 1 < bytes.len() {
-                // skip escaped char
-            } else if b == b'"' {
-                self.in_double_str = false;
-            }
-            return;
-        }
-
-        if b == b'/' && i + 1 < bytes.len() {
-            if bytes[i + 1] == b'/' {
-                self.in_line_comment = true;
-                return;
-            }
-            if bytes[i + 1] == b'*' {
-                self.in_block_comment = true;
-                return;
-            }
-        }
-        if b == b'\'' {
-            self.in_single_str = true;
-            return;
-        }
-        if b == b'"' {
-            self.in_double_str = true;
-        }
-    }
-
-    const fn in_comment_or_string(&self) -> bool {
-        self.in_line_comment || self.in_block_comment || self.in_single_str || self.in_double_str
-    }
-}
-
-fn match_paren(bytes: &[u8], open: usize) -> Option<usize> {
+fn match_paren(bytes: &[u8], classes: &ClassMap, open: usize) -> Option<usize> {
     let mut depth: isize = 0;
     let mut i = open;
     let len = bytes.len();
     while i < len {
         let b = bytes[i];
+        if !classes.is_code(i) {
+            i += 1;
+            continue;
+        }
         if b == b'(' {
             depth += 1;
         } else if b == b')' {
@@ -95,12 +31,16 @@ fn match_paren(bytes: &[u8], open: usize) -> Option<usize> {
     None
 }
 
-fn match_brace(bytes: &[u8], open: usize) -> Option<usize> {
+fn match_brace(bytes: &[u8], classes: &ClassMap, open: usize) -> Option<usize> {
     let mut depth: isize = 0;
     let mut i = open;
     let len = bytes.len();
     while i < len {
         let b = bytes[i];
+        if !classes.is_code(i) {
+            i += 1;
+            continue;
+        }
         if b == b'{' {
             depth += 1;
         } else if b == b'}' {
@@ -116,7 +56,12 @@ fn match_brace(bytes: &[u8], open: usize) -> Option<usize> {
 
 /// .collect({ |x| ... })  →  .collect { |x| ... }
 /// (same for select/reject/inject/detect)
-fn rewrite_method_trailing_block(bytes: &[u8], dot: usize, name: &str) -> Option<TextEdit> {
+fn rewrite_method_trailing_block(
+    bytes: &[u8],
+    classes: &ClassMap,
+    dot: usize,
+    name: &str,
+) -> Option<TextEdit> {
     let len = bytes.len();
     let name_bytes = name.as_bytes();
     let name_len = name_bytes.len();
@@ -145,7 +90,7 @@ fn rewrite_method_trailing_block(bytes: &[u8], dot: usize, name: &str) -> Option
         return None;
     }
     let open = i;
-    let close = match_paren(bytes, open)?;
+    let close = match_paren(bytes, classes, open)?;
 
     // Trim content inside parens.
     let mut inner_start = open + 1;
@@ -174,7 +119,7 @@ fn rewrite_method_trailing_block(bytes: &[u8], dot: usize, name: &str) -> Option
     if bytes[inner_start] != b'{' {
         return None;
     }
-    let block_end = match_brace(bytes, inner_start)?;
+    let block_end = match_brace(bytes, classes, inner_start)?;
     if block_end + 1 != inner_end {
         // Extra stuff after block → more args, bail.
         return None;
@@ -223,7 +168,7 @@ fn is_keyword_at(bytes: &[u8], idx: usize, kw: &[u8]) -> bool {
 }
 
 /// while({ cond }, { body })  →  while { cond } { body }
-fn rewrite_while_call(bytes: &[u8], idx: usize) -> Option<TextEdit> {
+fn rewrite_while_call(bytes: &[u8], classes: &ClassMap, idx: usize) -> Option<TextEdit> {
     if !is_keyword_at(bytes, idx, b"while") {
         return None;
     }
@@ -241,7 +186,7 @@ fn rewrite_while_call(bytes: &[u8], idx: usize) -> Option<TextEdit> {
         return None;
     }
     let open = i;
-    let close = match_paren(bytes, open)?;
+    let close = match_paren(bytes, classes, open)?;
 
     // Parse: while({cond}, {body})
     let mut p = open + 1;
@@ -254,7 +199,7 @@ fn rewrite_while_call(bytes: &[u8], idx: usize) -> Option<TextEdit> {
         return None;
     }
     let cond_start = p;
-    let cond_end = match_brace(bytes, cond_start)?;
+    let cond_end = match_brace(bytes, classes, cond_start)?;
 
     let mut q = cond_end + 1;
     while q < close
@@ -276,7 +221,7 @@ fn rewrite_while_call(bytes: &[u8], idx: usize) -> Option<TextEdit> {
         return None;
     }
     let body_start = q;
-    let body_end = match_brace(bytes, body_start)?;
+    let body_end = match_brace(bytes, classes, body_start)?;
 
     let mut r = body_end + 1;
     while r < close
@@ -307,17 +252,31 @@ impl Rule for ExtraTrailingClosures {
         "extra_trailing_closures"
     }
 
+    fn description(&self) -> &'static str {
+        "Move a final function argument out of the parentheses as a trailing closure."
+    }
+
+    fn category(&self) -> Category {
+        Category::Semantic
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "[1, 2].collect({ |x| x * 2 });\n",
+            after: "[1, 2].collect { |x| x * 2 };\n",
+        }
+    }
+
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
         let src = cx.bytes();
         let bytes: &[u8] = &src;
         let len = bytes.len();
+        let classes = cx.classes();
         let mut edits: Vec<TextEdit> = Vec::new();
-        let mut st = ScanState::default();
 
         let mut i = 0usize;
         while i < len {
-            st.step(bytes, i);
-            if st.in_comment_or_string() {
+            if !classes.is_code(i) {
                 i += 1;
                 continue;
             }
@@ -329,7 +288,7 @@ impl Rule for ExtraTrailingClosures {
                 if b == b'.' {
                     // Try each method name.
                     for &name in &["collect", "select", "reject", "inject", "detect"] {
-                        if let Some(edit) = rewrite_method_trailing_block(bytes, i, name) {
+                        if let Some(edit) = rewrite_method_trailing_block(bytes, classes, i, name) {
                             let jump = edit.end_byte;
                             edits.push(edit);
                             i = jump;
@@ -342,7 +301,7 @@ impl Rule for ExtraTrailingClosures {
                 i += 1;
                 continue;
             } else if b == b'w'
-                && let Some(edit) = rewrite_while_call(bytes, i)
+                && let Some(edit) = rewrite_while_call(bytes, classes, i)
             {
                 let jump = edit.end_byte;
                 edits.push(edit);
@@ -0,0 +1,41 @@
+use crate::engine::{Ctx, TextEdit};
+use crate::rules::{Category, Example, Rule};
+use anyhow::Result;
+
+pub struct EnsureFinalNewline;
+
+impl Rule for EnsureFinalNewline {
+    fn name(&self) -> &'static str {
+        "final_newline"
+    }
+
+    fn description(&self) -> &'static str {
+        "End a non-empty file with exactly one newline."
+    }
+
+    fn category(&self) -> Category {
+        Category::Cleanup
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "x = 1;\n\n\n",
+            after: "x = 1;\n",
+        }
+    }
+
+    fn run(&self, cx: &mut Ctx) -> Result<usize> {
+        let bytes = cx.bytes();
+        let end = bytes.len();
+        let content_end = bytes.iter().rposition(|&b| b != b'\n').map_or(0, |i| i + 1);
+        if content_end == 0 || bytes[content_end..] == *b"\n" {
+            return Ok(0);
+        }
+        cx.apply_edits(vec![TextEdit {
+            start_byte: content_end,
+            end_byte: end,
+            replacement: "\n".into(),
+        }])?;
+        Ok(1)
+    }
+}
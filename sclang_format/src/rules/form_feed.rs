@@ -0,0 +1,54 @@
+use crate::engine::{Ctx, TextEdit};
+use crate::rules::{Category, Example, Rule};
+use anyhow::Result;
+
+const FORM_FEED: u8 = 0x0C;
+
+pub struct RemoveFormFeeds;
+
+impl Rule for RemoveFormFeeds {
+    fn name(&self) -> &'static str {
+        "form_feeds"
+    }
+
+    fn description(&self) -> &'static str {
+        "Remove form feeds from code; one between two tokens becomes a space."
+    }
+
+    fn category(&self) -> Category {
+        Category::Normalize
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "x = 1;\n\u{c}\ny = 2;\n",
+            after: "x = 1;\n\ny = 2;\n",
+        }
+    }
+
+    fn run(&self, cx: &mut Ctx) -> Result<usize> {
+        let bytes = cx.bytes();
+        let classes = cx.classes();
+        let blank = |b: Option<&u8>| b.is_none_or(|&b| b.is_ascii_whitespace());
+
+        let mut edits = Vec::new();
+        for (i, _) in bytes.iter().enumerate().filter(|&(_, &b)| b == FORM_FEED) {
+            if !classes.is_code(i) {
+                continue;
+            }
+            let before = i.checked_sub(1).and_then(|j| bytes.get(j));
+            let separates = !blank(before) && !blank(bytes.get(i + 1));
+            edits.push(TextEdit {
+                start_byte: i,
+                end_byte: i + 1,
+                replacement: if separates { " ".into() } else { String::new() },
+            });
+        }
+
+        let n = edits.len();
+        if n > 0 {
+            cx.apply_edits(edits)?;
+        }
+        Ok(n)
+    }
+}
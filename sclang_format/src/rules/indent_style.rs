@@ -1,5 +1,5 @@
 use crate::engine::{Ctx, TextEdit};
-use crate::rules::Rule;
+use crate::rules::{Category, Example, Rule};
 use anyhow::Result;
 
 const fn is_ws(b: u8) -> bool {
@@ -13,6 +13,21 @@ impl Rule for IndentStyleRule {
         "indent_style"
     }
 
+    fn description(&self) -> &'static str {
+        "Convert leading whitespace to the configured indent style."
+    }
+
+    fn category(&self) -> Category {
+        Category::Spacing
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "\tx = 1;\n",
+            after: "    x = 1;\n",
+        }
+    }
+
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
         let bytes = cx.bytes();
         let len = bytes.len();
@@ -1,7 +1,7 @@
 // src/rules/inline_comment_spacing.rs
 
-use crate::engine::{Ctx, TextEdit};
-use crate::rules::Rule;
+use crate::engine::{ByteClass, Ctx, TextEdit};
+use crate::rules::{Category, Example, Rule};
 use anyhow::Result;
 
 const fn is_space(b: u8) -> bool {
@@ -12,42 +12,6 @@ const fn is_newline(b: u8) -> bool {
     b == b'\n' || b == b'\r'
 }
 
-// Very simple "are we inside a string up to idx?" check, just to
-// avoid touching `//` inside `"..."` or `'...'`.
-fn is_string_context(bytes: &[u8], idx: usize) -> bool {
-    let mut in_single = false;
-    let mut in_double = false;
-    let mut i = 0usize;
-
-    while i < idx && i < bytes.len() {
-        let b = bytes[i];
-
-        if in_single {
-            if b == b'\\' && i + 1 < idx {
-                i += 2;
-                continue;
-            } else if b == b'\'' {
-                in_single = false;
-            }
-        } else if in_double {
-            if b == b'\\' && i + 1 < idx {
-                i += 2;
-                continue;
-            } else if b == b'"' {
-                in_double = false;
-            }
-        } else if b == b'\'' {
-            in_single = true;
-        } else if b == b'"' {
-            in_double = true;
-        }
-
-        i += 1;
-    }
-
-    in_single || in_double
-}
-
 pub struct InlineCommentSpacing;
 
 impl Rule for InlineCommentSpacing {
@@ -55,21 +19,35 @@ impl Rule for InlineCommentSpacing {
         "inline_comment_spacing"
     }
 
+    fn description(&self) -> &'static str {
+        "Two spaces before a `//` comment that follows code."
+    }
+
+    fn category(&self) -> Category {
+        Category::Spacing
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "x = 1;// note\n",
+            after: "x = 1;  // note\n",
+        }
+    }
+
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
         let src = cx.bytes();
         let bytes: &[u8] = &src;
         let len = bytes.len();
+        let classes = cx.classes();
         let mut edits: Vec<TextEdit> = Vec::new();
 
         let mut i = 0usize;
         while i + 1 < len {
-            if bytes[i] == b'/' && bytes[i + 1] == b'/' {
-                // Skip `//` inside strings.
-                if is_string_context(bytes, i) {
-                    i += 2;
-                    continue;
-                }
-
+            // Only the `//` that opens a line comment; not one inside a
+            // string, a block comment or the comment text itself.
+            let opens_comment = classes.class(i) == ByteClass::LineComment
+                && (i == 0 || classes.class(i - 1) != ByteClass::LineComment);
+            if opens_comment {
                 // Find start of line.
                 let mut line_start = i;
                 while line_start > 0 && bytes[line_start - 1] != b'\n' {
@@ -1,5 +1,5 @@
 use crate::engine::{Ctx, TextEdit};
-use crate::rules::Rule;
+use crate::rules::{Category, Example, Rule};
 use anyhow::{Result, Ok};
 
 /// Simple whitespace predicates
@@ -48,26 +48,26 @@ fn is_assignment_equal(src: &[u8], i: usize) -> bool {
     true
 }
 
-/// Check if the quote at position `i` is escaped with an odd number of backslashes.
-fn is_escaped(src: &[u8], i: usize) -> bool {
-    let mut count = 0;
-    let mut j = i;
-    while j > 0 {
-        j -= 1;
-        if src[j] == b'\\' {
-            count += 1;
-        } else {
-            break;
-        }
-    }
-    count % 2 == 1
-}
-
 pub struct InlineWhitespaceFormat;
 
 impl Rule for InlineWhitespaceFormat {
     fn name(&self) -> &'static str {
-        "InlineWhitespaceFormat"
+        "inline_whitespace"
+    }
+
+    fn description(&self) -> &'static str {
+        "Collapse runs of spaces between tokens to one."
+    }
+
+    fn category(&self) -> Category {
+        Category::Spacing
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "x  =   1;\n",
+            after: "x = 1;\n",
+        }
     }
 
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
@@ -78,73 +78,13 @@ impl Rule for InlineWhitespaceFormat {
 
         let mut i = 0usize;
 
-        // Very small lexical state machine so we don't mangle strings/comments.
-        let mut in_line_comment = false;
-        let mut in_block_comment = false;
-        let mut in_single_str = false;
-        let mut in_double_str = false;
+        let classes = cx.classes();
 
         while i < len {
             let b = src[i];
 
-            // --- handle being *inside* comments/strings first ---
-
-            if in_line_comment {
-                if b == b'\n' {
-                    in_line_comment = false;
-                }
-                i += 1;
-                continue;
-            }
-
-            if in_block_comment {
-                if b == b'*' && i + 1 < len && src[i + 1] == b'/' {
-                    in_block_comment = false;
-                    i += 2;
-                } else {
-                    i += 1;
-                }
-                continue;
-            }
-
-            if in_single_str {
-                if b == b'\'' && !is_escaped(&src, i) {
-                    in_single_str = false;
-                }
-                i += 1;
-                continue;
-            }
-
-            if in_double_str {
-                if b == b'"' && !is_escaped(&src, i) {
-                    in_double_str = false;
-                }
-                i += 1;
-                continue;
-            }
-
-            // --- we are in "code" now: detect starts of comments/strings ---
-
-            if b == b'/' && i + 1 < len {
-                if src[i + 1] == b'/' {
-                    in_line_comment = true;
-                    i += 2;
-                    continue;
-                } else if src[i + 1] == b'*' {
-                    in_block_comment = true;
-                    i += 2;
-                    continue;
-                }
-            }
-
-            if b == b'\'' {
-                in_single_str = true;
-                i += 1;
-                continue;
-            }
-
-            if b == b'"' {
-                in_double_str = true;
+            // Leave strings, symbols and comments alone.
+            if !classes.is_code(i) {
                 i += 1;
                 continue;
             }
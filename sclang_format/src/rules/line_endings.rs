@@ -0,0 +1,54 @@
+use crate::engine::{ByteClass, Ctx, TextEdit};
+use crate::rules::{Category, Example, Rule};
+use anyhow::Result;
+
+pub struct NormalizeLineEndings;
+
+impl Rule for NormalizeLineEndings {
+    fn name(&self) -> &'static str {
+        "line_endings"
+    }
+
+    fn description(&self) -> &'static str {
+        "End every line with `\\n`, turning `\\r\\n` and lone `\\r` into it."
+    }
+
+    fn category(&self) -> Category {
+        Category::Normalize
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "x = 1;\r\ny = 2;\r\n",
+            after: "x = 1;\ny = 2;\n",
+        }
+    }
+
+    fn run(&self, cx: &mut Ctx) -> Result<usize> {
+        let bytes = cx.bytes();
+        let classes = cx.classes();
+        let mut edits = Vec::new();
+        for (i, _) in bytes.iter().enumerate().filter(|&(_, &b)| b == b'\r') {
+            // A `\r` inside a string, symbol or character literal is part of
+            // its value. Comments are normalized like code.
+            if matches!(
+                classes.class(i),
+                ByteClass::String | ByteClass::Symbol | ByteClass::Char
+            ) {
+                continue;
+            }
+            let crlf = bytes.get(i + 1) == Some(&b'\n');
+            edits.push(TextEdit {
+                start_byte: i,
+                end_byte: i + 1,
+                replacement: if crlf { String::new() } else { "\n".into() },
+            });
+        }
+
+        let n = edits.len();
+        if n > 0 {
+            cx.apply_edits(edits)?;
+        }
+        Ok(n)
+    }
+}
@@ -0,0 +1,79 @@
+use crate::engine::{Ctx, IndentStyle, TextEdit};
+use crate::rules::{Category, Example, Rule};
+use anyhow::Result;
+
+/// Tab stops assumed when the configured style is tabs.
+const TAB_STOP: usize = 4;
+
+pub struct MixedIndentation;
+
+impl Rule for MixedIndentation {
+    fn name(&self) -> &'static str {
+        "mixed_indentation"
+    }
+
+    fn description(&self) -> &'static str {
+        "Rewrite indentation that mixes tabs and spaces in the configured style, \
+         keeping its width."
+    }
+
+    fn category(&self) -> Category {
+        Category::Normalize
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "{\n  \tx = 1;\n}\n",
+            after: "{\n    x = 1;\n}\n",
+        }
+    }
+
+    fn run(&self, cx: &mut Ctx) -> Result<usize> {
+        let bytes = cx.bytes();
+        let stop = match cx.indent_style {
+            IndentStyle::Tabs => TAB_STOP,
+            IndentStyle::Spaces { width } => width.max(1),
+        };
+
+        let mut edits = Vec::new();
+        let mut line_start = 0;
+        while line_start < bytes.len() {
+            let line_end = bytes[line_start..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(bytes.len(), |i| line_start + i);
+            let lead = bytes[line_start..line_end]
+                .iter()
+                .take_while(|&&b| b == b' ' || b == b'\t')
+                .count();
+            let indent = &bytes[line_start..line_start + lead];
+            // Lines continuing a string or block comment are not indentation.
+            if indent.contains(&b'\t') && indent.contains(&b' ') && cx.classes().is_code(line_start)
+            {
+                let cols = indent.iter().fold(0, |col, &b| {
+                    if b == b'\t' {
+                        (col / stop + 1) * stop
+                    } else {
+                        col + 1
+                    }
+                });
+                let replacement = match cx.indent_style {
+                    IndentStyle::Tabs => "\t".repeat(cols / stop) + &" ".repeat(cols % stop),
+                    IndentStyle::Spaces { .. } => " ".repeat(cols),
+                };
+                edits.push(TextEdit {
+                    start_byte: line_start,
+                    end_byte: line_start + lead,
+                    replacement,
+                });
+            }
+            line_start = line_end + 1;
+        }
+
+        let n = edits.len();
+        if n > 0 {
+            cx.apply_edits(edits)?;
+        }
+        Ok(n)
+    }
+}
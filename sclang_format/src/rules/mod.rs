@@ -1,9 +1,69 @@
+use crate::config::RuleSelection;
+use crate::diagnostic::NewSyntaxErrors;
 use crate::engine::Ctx;
-use anyhow::Result;
+use crate::error::Error;
+use crate::verify::{self, Snapshot};
+use anyhow::{Result, bail};
+use std::fmt;
+
+/// What kind of change a rule makes. Roughly follows the pipeline groups
+/// in [`registry`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Category {
+    /// Evens out the raw input before the pipeline sees it (pre phase).
+    Normalize,
+    /// Rewrites syntax into an equivalent form (`arg` → pipes, closures).
+    Semantic,
+    /// Moves braces, dots and collection elements between lines.
+    Structural,
+    /// Whitespace within a line, plus indentation.
+    Spacing,
+    /// Joins or splits lines depending on the maximum line width.
+    WidthAware,
+    /// Final tidying once everything else has run.
+    Cleanup,
+}
+
+impl Category {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Normalize => "normalize",
+            Self::Semantic => "semantic",
+            Self::Structural => "structural",
+            Self::Spacing => "spacing",
+            Self::WidthAware => "width-aware",
+            Self::Cleanup => "cleanup",
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+/// Input a rule changes and what it produces when run on its own.
+/// `tests/rule_examples.rs` keeps these honest.
+#[derive(Clone, Copy, Debug)]
+pub struct Example {
+    pub before: &'static str,
+    pub after: &'static str,
+}
 
 pub trait Rule {
     fn name(&self) -> &'static str;
+    /// One-line summary for `--list-rules`.
+    fn description(&self) -> &'static str;
+    fn category(&self) -> Category;
+    fn example(&self) -> Example;
     fn run(&self, cx: &mut Ctx) -> Result<usize>;
+
+    /// Whether the rule runs unless a selection says otherwise.
+    fn default_enabled(&self) -> bool {
+        true
+    }
 }
 
 mod arg_to_pipe;
@@ -12,6 +72,7 @@ mod ast_indent;
 mod binary_ops;
 mod block_brace;
 mod block_layout;
+mod bom;
 mod brace_pipes;
 mod call_index_paren;
 mod colons;
@@ -23,10 +84,14 @@ mod dot_chain_layout;
 mod events_multiline;
 mod expand_if_trailing;
 mod extra_trailing_closures;
+mod final_newline;
+mod form_feed;
 mod indent_style;
 mod inline_comment_spacing;
 mod inline_ws;
 mod keyword_paren;
+mod line_endings;
+mod mixed_indent;
 mod multiline_arrays;
 mod no_final_semicolon;
 mod parens_brackets;
@@ -46,6 +111,7 @@ pub use ast_indent::IndentByAstLevel;
 pub use binary_ops::AddSpacesAroundBinaryOps;
 pub use block_brace::BlockBraceSpacing;
 pub use block_layout::BlockLayoutKAndR;
+pub use bom::StripByteOrderMark;
 pub use brace_pipes::BraceAndPipesSingleLine;
 pub use call_index_paren::CallIndexParenSpacing;
 pub use colons::AddSpacesAroundColons;
@@ -57,10 +123,14 @@ pub use dot_chain_layout::DotChainLayout;
 pub use events_multiline::MultiLineEventsOnePerLine;
 pub use expand_if_trailing::ExpandLongIfTrailingClosures;
 pub use extra_trailing_closures::ExtraTrailingClosures;
+pub use final_newline::EnsureFinalNewline;
+pub use form_feed::RemoveFormFeeds;
 pub use indent_style::IndentStyleRule;
 pub use inline_comment_spacing::InlineCommentSpacing;
 pub use inline_ws::InlineWhitespaceFormat;
 pub use keyword_paren::KeywordParenSpacing;
+pub use line_endings::NormalizeLineEndings;
+pub use mixed_indent::MixedIndentation;
 pub use multiline_arrays::MultiLineArrayElementsPerLine;
 pub use no_final_semicolon::NoFinalSemicolon;
 pub use parens_brackets::ParenBracketSpacing;
@@ -74,12 +144,38 @@ pub use trailing_closures::TrailingClosures;
 pub use trailing_ws::TrimTrailingWhitespaceAndEofNewline;
 pub use var_arg::VarAndArgSpacing;
 
-pub const fn run_pre(_cx: &mut Ctx) -> Result<()> {
-    Ok(())
+/// The pre phase, in order. These work on raw bytes, so they are safe on
+/// input that does not parse, and run before syntax errors are looked for:
+///
+/// 1. `byte_order_mark`: drop a leading UTF-8 BOM;
+/// 2. `line_endings`: `\r\n` and lone `\r` become `\n`;
+/// 3. `form_feeds`: form feeds outside literals and comments go;
+/// 4. `mixed_indentation`: indentation mixing tabs and spaces is rewritten
+///    in the configured style.
+#[must_use]
+pub fn pre_registry() -> Vec<Box<dyn Rule>> {
+    vec![
+        Box::new(StripByteOrderMark),
+        Box::new(NormalizeLineEndings),
+        Box::new(RemoveFormFeeds),
+        Box::new(MixedIndentation),
+    ]
 }
 
-pub fn run_inline(cx: &mut Ctx) -> Result<()> {
-    let rules: Vec<Box<dyn Rule>> = vec![
+/// The post phase's rules, in order. After them, `run_post_with` reparses
+/// the result from scratch and fails if input that parsed no longer does:
+///
+/// 1. `final_newline`: end the file with exactly one newline.
+#[must_use]
+pub fn post_registry() -> Vec<Box<dyn Rule>> {
+    vec![Box::new(EnsureFinalNewline)]
+}
+
+/// Every rule, in pipeline order. Selections only ever remove entries
+/// (or switch on off-by-default ones); they never reorder.
+#[must_use]
+pub fn registry() -> Vec<Box<dyn Rule>> {
+    vec![
         // 1. Semantic / AST-level transforms
         Box::new(ArgToPipeParams),
         Box::new(TrailingClosures),
@@ -110,6 +206,7 @@ pub fn run_inline(cx: &mut Ctx) -> Result<()> {
         Box::new(BraceAndPipesSingleLine),
         Box::new(InlineCommentSpacing),
         // 5. Indentation / global inline whitespace
+        Box::new(IndentByAstLevel),
         Box::new(IndentStyleRule),
         Box::new(InlineWhitespaceFormat),
         // 6. Width-aware 80-col logic
@@ -119,14 +216,204 @@ pub fn run_inline(cx: &mut Ctx) -> Result<()> {
         // 7. Final clean-ups
         Box::new(NoFinalSemicolon),
         Box::new(TrimTrailingWhitespaceAndEofNewline),
-    ];
+    ]
+}
+
+/// Every rule of every phase: pre, inline, post, each in order.
+#[must_use]
+pub fn all() -> Vec<Box<dyn Rule>> {
+    let mut all = pre_registry();
+    all.extend(registry());
+    all.extend(post_registry());
+    all
+}
+
+/// Names of all registered rules, in the order [`all`] gives.
+#[must_use]
+pub fn names() -> Vec<&'static str> {
+    all().iter().map(|r| r.name()).collect()
+}
+
+/// Look up a registered rule by name.
+#[must_use]
+pub fn find(name: &str) -> Option<Box<dyn Rule>> {
+    all().into_iter().find(|r| r.name() == name)
+}
+
+/// The inline rules `selection` turns on, in pipeline order.
+///
+/// # Errors
+/// Fails if the selection names a rule that does not exist in any phase.
+pub fn select(selection: &RuleSelection) -> Result<Vec<Box<dyn Rule>>> {
+    select_from(registry(), selection)
+}
+
+fn select_from(rules: Vec<Box<dyn Rule>>, selection: &RuleSelection) -> Result<Vec<Box<dyn Rule>>> {
+    let known = names();
+    for name in selection.names() {
+        if !known.contains(&name) {
+            bail!("unknown rule `{name}` (known rules: {})", known.join(", "));
+        }
+    }
+    Ok(rules
+        .into_iter()
+        .filter(|r| selection.is_enabled(r.name(), r.default_enabled()))
+        .collect())
+}
+
+/// [`select_from`] for the pipeline runners, which report an unknown name
+/// as bad options.
+fn selected(
+    rules: Vec<Box<dyn Rule>>,
+    selection: &RuleSelection,
+) -> Result<Vec<Box<dyn Rule>>, Error> {
+    select_from(rules, selection).map_err(|e| Error::InvalidOptions(e.to_string()))
+}
+
+/// Run `rules` once each, in order.
+fn run_once(cx: &mut Ctx, rules: &[Box<dyn Rule>]) -> Result<(), Error> {
     for r in rules {
-        let _ = r.run(cx)?;
+        cx.set_rule(Some(r.name()));
+        let before = cx.verify.then(|| Snapshot::of(cx));
+        let _ = r.run(cx).map_err(|e| Error::from_rule(r.name(), e))?;
+        if let Some(before) = before {
+            verify::compare(&before, &Snapshot::of(cx), Some(r.name()))?;
+        }
     }
+    cx.set_rule(None);
     Ok(())
 }
 
-pub const fn run_post(_cx: &mut Ctx) -> Result<()> {
+/// Run the default pre phase rules.
+///
+/// # Errors
+/// As [`run_pre_with`].
+pub fn run_pre(cx: &mut Ctx) -> Result<(), Error> {
+    run_pre_with(cx, &RuleSelection::default())
+}
+
+/// Run the pre phase rules `selection` turns on.
+///
+/// # Errors
+/// Fails with [`Error::InvalidOptions`] on an unknown rule name, or if a
+/// rule fails.
+pub fn run_pre_with(cx: &mut Ctx, selection: &RuleSelection) -> Result<(), Error> {
+    run_once(cx, &selected(pre_registry(), selection)?)
+}
+
+/// Run the default post phase rules.
+///
+/// # Errors
+/// As [`run_post_with`].
+pub fn run_post(cx: &mut Ctx) -> Result<(), Error> {
+    run_post_with(cx, &RuleSelection::default())
+}
+
+/// Run the post phase rules `selection` turns on, then reparse the buffer
+/// from scratch and check that formatting did not break it.
+///
+/// # Errors
+/// Fails with [`Error::InvalidOptions`] on an unknown rule name, if a rule
+/// fails, or with [`Error::BrokenOutput`] if the input parsed and the
+/// result does not.
+pub fn run_post_with(cx: &mut Ctx, selection: &RuleSelection) -> Result<(), Error> {
+    run_once(cx, &selected(post_registry(), selection)?)?;
+    cx.reparse_from_scratch()?;
+    // Error recovery reshapes the tree around every edit, so counts from
+    // input that was already broken say little; only clean input is held
+    // to a clean result.
+    if cx.input_errors() == 0 && cx.tree.root_node().has_error() {
+        return Err(NewSyntaxErrors(cx.syntax_errors()).into());
+    }
+    Ok(())
+}
+
+/// Run the default inline phase rules.
+///
+/// # Errors
+/// As [`run_inline_with`].
+pub fn run_inline(cx: &mut Ctx) -> Result<(), Error> {
+    run_inline_with(cx, &RuleSelection::default())
+}
+
+/// The pipeline was still changing the buffer after `passes` passes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NonConvergence {
+    pub passes: usize,
+    /// Rules that changed something in the last pass, in pipeline order.
+    pub rules: Vec<&'static str>,
+}
+
+impl fmt::Display for NonConvergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "formatting did not settle after {} passes; still changing: {}",
+            self.passes,
+            self.rules.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for NonConvergence {}
+
+/// Run the inline pipeline with the rules `selection` turns on until a
+/// whole pass leaves the buffer as it found it.
+///
+/// Later rules can open up work for earlier ones (a compacted collection
+/// may now need spacing), so a single pass is not always stable. Only
+/// passes that change the buffer count against `cx.max_passes`; the one
+/// after them just confirms there is nothing left to do. If it finds more,
+/// its output is kept all the same, and a
+/// [`crate::Warning::NonConvergence`] naming the rules still changing is
+/// added to [`Ctx::warnings`], unless `cx.strict_convergence` makes it an
+/// error.
+///
+/// # Errors
+/// Fails with [`Error::InvalidOptions`] on an unknown rule name, if a rule
+/// fails, or with [`Error::NonConvergence`] if the buffer has not settled
+/// and `cx.strict_convergence` is set.
+pub fn run_inline_with(cx: &mut Ctx, selection: &RuleSelection) -> Result<(), Error> {
+    let rules = selected(registry(), selection)?;
+    let passes = cx.max_passes;
+    let mut changed = Vec::new();
+    // The passes allowed to change the buffer, and the confirming one.
+    for _ in 0..=passes {
+        changed.clear();
+        let start = cx.to_string();
+        for r in &rules {
+            cx.set_rule(Some(r.name()));
+            let revision = cx.revision();
+            let before = cx.verify.then(|| Snapshot::of(cx));
+            let _ = r.run(cx).map_err(|e| Error::from_rule(r.name(), e))?;
+            if cx.revision() == revision {
+                continue;
+            }
+            changed.push(r.name());
+            if let Some(before) = before {
+                verify::compare(&before, &Snapshot::of(cx), Some(r.name()))?;
+            }
+        }
+        // Rules may undo each other within a pass (one wants a single space
+        // after a comma, another two before a comment); only the end
+        // result counts.
+        if changed.is_empty() || cx.to_string() == start {
+            changed.clear();
+            break;
+        }
+    }
+    cx.set_rule(None);
+    if changed.is_empty() {
+        return Ok(());
+    }
+    let unsettled = NonConvergence {
+        passes,
+        rules: changed,
+    };
+    if cx.strict_convergence {
+        return Err(unsettled.into());
+    }
+    cx.warn(unsettled);
     Ok(())
 }
 
@@ -1,25 +1,11 @@
 use crate::engine::{Ctx, TextEdit};
-use crate::rules::Rule;
+use crate::rules::{Category, Example, Rule};
 use anyhow::Result;
-use tree_sitter::Node;
 
 const fn is_space_or_tab(b: u8) -> bool {
     b == b' ' || b == b'\t'
 }
 
-/// AST-based “are we in a string/comment?”
-fn in_comment_or_string(root: &Node, byte: usize) -> bool {
-    let mut cur = root.descendant_for_byte_range(byte, byte + 1);
-    while let Some(n) = cur {
-        match n.kind() {
-            "comment" | "block_comment" | "line_comment" | "string" => return true,
-            _ => {}
-        }
-        cur = n.parent();
-    }
-    false
-}
-
 pub struct MultiLineArrayElementsPerLine;
 
 impl Rule for MultiLineArrayElementsPerLine {
@@ -27,10 +13,25 @@ impl Rule for MultiLineArrayElementsPerLine {
         "multiline_array_elements_per_line"
     }
 
+    fn description(&self) -> &'static str {
+        "Put each element of a multi-line array on its own line."
+    }
+
+    fn category(&self) -> Category {
+        Category::Structural
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "a = [\n    1,2\n];\n",
+            after: "a = [\n    1,\n    2\n];\n",
+        }
+    }
+
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
         let bytes = cx.bytes();
         let buf: &[u8] = &bytes;
-        let root = cx.tree.root_node();
+        let classes = cx.classes();
         let len = buf.len();
 
         let mut edits: Vec<TextEdit> = Vec::new();
@@ -39,7 +40,7 @@ impl Rule for MultiLineArrayElementsPerLine {
         while i < len {
             if buf[i] == b'[' {
                 // skip arrays that are inside comments/strings
-                if in_comment_or_string(&root, i) {
+                if !classes.is_code(i) {
                     i += 1;
                     continue;
                 }
@@ -1,20 +1,6 @@
 use crate::engine::{Ctx, TextEdit};
-use crate::rules::Rule;
+use crate::rules::{Category, Example, Rule};
 use anyhow::Result;
-use tree_sitter::Node;
-
-/// AST-based “are we in a string/comment?”
-fn in_comment_or_string(root: &Node, byte: usize) -> bool {
-    let mut cur = root.descendant_for_byte_range(byte, byte + 1);
-    while let Some(n) = cur {
-        match n.kind() {
-            "comment" | "block_comment" | "line_comment" | "string" => return true,
-            _ => {}
-        }
-        cur = n.parent();
-    }
-    false
-}
 
 pub struct NoFinalSemicolon;
 
@@ -23,10 +9,25 @@ impl Rule for NoFinalSemicolon {
         "no_final_semicolon_before_brace"
     }
 
+    fn description(&self) -> &'static str {
+        "Drop the semicolon after the last statement of a block."
+    }
+
+    fn category(&self) -> Category {
+        Category::Cleanup
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "f = { 1; };\n",
+            after: "f = { 1 };\n",
+        }
+    }
+
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
         let bytes = cx.bytes();
         let buf: &[u8] = &bytes;
-        let root = cx.tree.root_node();
+        let classes = cx.classes();
         let len = buf.len();
 
         let mut edits: Vec<TextEdit> = Vec::new();
@@ -37,7 +38,7 @@ impl Rule for NoFinalSemicolon {
             }
 
             // Don't touch braces inside strings/comments.
-            if in_comment_or_string(&root, i) {
+            if !classes.is_code(i) {
                 continue;
             }
 
@@ -51,7 +52,7 @@ impl Rule for NoFinalSemicolon {
                 }
 
                 // If that char is a ';' and not in a comment/string, drop it.
-                if c == b';' && !in_comment_or_string(&root, j) {
+                if c == b';' && classes.is_code(j) {
                     edits.push(TextEdit {
                         start_byte: j,
                         end_byte: j + 1,
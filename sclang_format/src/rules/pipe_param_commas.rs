@@ -1,7 +1,6 @@
-use crate::engine::{Ctx, TextEdit};
-use crate::rules::Rule;
+use crate::engine::{ClassMap, Ctx, TextEdit};
+use crate::rules::{Category, Example, Rule};
 use anyhow::Result;
-use tree_sitter::Node;
 
 const fn is_space(b: u8) -> bool {
     b == b' ' || b == b'\t'
@@ -18,31 +17,18 @@ fn is_assignment_eq(seg: &[u8], idx: usize) -> bool {
     !(prev == b'=' || prev == b'<' || prev == b'>' || prev == b'!') && next != b'='
 }
 
-/// AST-based “are we in a string/comment?”
-fn in_comment_or_string(root: &Node, byte: usize) -> bool {
-    let mut cur = root.descendant_for_byte_range(byte, byte + 1);
-    while let Some(n) = cur {
-        match n.kind() {
-            "comment" | "block_comment" | "line_comment" | "string" => return true,
-            _ => {}
-        }
-        cur = n.parent();
-    }
-    false
-}
-
 /// Find `| ... |` header segments that are *actually* pipe param lists:
 /// - opening `|` not in string/comment
 /// - `{` earlier on the same line
 /// - matching closing `|` on the same line, not in string/comment
-fn find_pipe_param_headers(buf: &[u8], root: &Node) -> Vec<(usize, usize)> {
+fn find_pipe_param_headers(buf: &[u8], classes: &ClassMap) -> Vec<(usize, usize)> {
     let len = buf.len();
     let mut res = Vec::new();
     let mut i = 0;
 
     while i < len {
         if buf[i] == b'|' {
-            if in_comment_or_string(root, i) {
+            if !classes.is_code(i) {
                 i += 1;
                 continue;
             }
@@ -76,7 +62,7 @@ fn find_pipe_param_headers(buf: &[u8], root: &Node) -> Vec<(usize, usize)> {
                     break;
                 }
                 if c == b'|' {
-                    if in_comment_or_string(root, k) {
+                    if !classes.is_code(k) {
                         break;
                     }
                     end_opt = Some(k);
@@ -204,14 +190,29 @@ impl Rule for PipeParamAddMissingCommas {
         "pipe_param_missing_commas"
     }
 
+    fn description(&self) -> &'static str {
+        "Insert missing commas between `|...|` parameters."
+    }
+
+    fn category(&self) -> Category {
+        Category::Semantic
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "f = { |a = 1 b = 2| a + b };\n",
+            after: "f = { |a = 1, b = 2| a + b };\n",
+        }
+    }
+
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
         let bytes = cx.bytes();
         let buf: &[u8] = &bytes;
-        let root = cx.tree.root_node();
+        let classes = cx.classes();
 
         let mut edits: Vec<TextEdit> = Vec::new();
 
-        for (pipe_l, pipe_r) in find_pipe_param_headers(buf, &root) {
+        for (pipe_l, pipe_r) in find_pipe_param_headers(buf, classes) {
             if pipe_r <= pipe_l + 1 {
                 continue;
             }
@@ -1,5 +1,5 @@
-use crate::engine::{Ctx, TextEdit};
-use crate::rules::Rule;
+use crate::engine::{ClassMap, Ctx, TextEdit};
+use crate::rules::{Category, Example, Rule};
 use anyhow::Result;
 
 /// Whitespace
@@ -120,31 +120,18 @@ fn is_assignment_eq(seg: &[u8], idx: usize) -> bool {
     !(prev == b'=' || prev == b'<' || prev == b'>' || prev == b'!') && next != b'='
 }
 
-/// AST-based “are we in a string/comment?”
-fn in_comment_or_string(root: &tree_sitter::Node, byte: usize) -> bool {
-    let mut cur = root.descendant_for_byte_range(byte, byte + 1);
-    while let Some(n) = cur {
-        match n.kind() {
-            "comment" | "block_comment" | "line_comment" | "string" => return true,
-            _ => {}
-        }
-        cur = n.parent();
-    }
-    false
-}
-
 /// Same header detection idea as in `pipe_param_commas`:
 /// - '|' not in string/comment
 /// - '{' earlier on same line
 /// - matching '|' on same line, not in string/comment
-fn find_pipe_param_headers(buf: &[u8], root: &tree_sitter::Node) -> Vec<(usize, usize)> {
+fn find_pipe_param_headers(buf: &[u8], classes: &ClassMap) -> Vec<(usize, usize)> {
     let len = buf.len();
     let mut res = Vec::new();
     let mut i = 0;
 
     while i < len {
         if buf[i] == b'|' {
-            if in_comment_or_string(root, i) {
+            if !classes.is_code(i) {
                 i += 1;
                 continue;
             }
@@ -178,7 +165,7 @@ fn find_pipe_param_headers(buf: &[u8], root: &tree_sitter::Node) -> Vec<(usize,
                     break;
                 }
                 if c == b'|' {
-                    if in_comment_or_string(root, k) {
+                    if !classes.is_code(k) {
                         break;
                     }
                     end_opt = Some(k);
@@ -207,14 +194,29 @@ impl Rule for PipeParamDefaultParens {
         "pipe_param_default_parens"
     }
 
+    fn description(&self) -> &'static str {
+        "Parenthesize parameter defaults that are expressions rather than literals."
+    }
+
+    fn category(&self) -> Category {
+        Category::Semantic
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "f = { |freq = 2 * 220| freq };\n",
+            after: "f = { |freq = (2 * 220)| freq };\n",
+        }
+    }
+
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
         let bytes = cx.bytes();
         let buf: &[u8] = &bytes;
-        let root = cx.tree.root_node();
+        let classes = cx.classes();
 
         let mut edits: Vec<TextEdit> = Vec::new();
 
-        for (pipe_l, pipe_r) in find_pipe_param_headers(buf, &root) {
+        for (pipe_l, pipe_r) in find_pipe_param_headers(buf, classes) {
             if pipe_r <= pipe_l + 1 {
                 continue;
             }
@@ -222,9 +224,13 @@ impl Rule for PipeParamDefaultParens {
             let seg_lo = pipe_l + 1;
             let seg_hi = pipe_r;
             let seg = &buf[seg_lo..seg_hi];
+            // End of the last default we looked at. With a comma missing
+            // (`|a = 1 b = 2|`) that default swallows the next `=`, which
+            // must not get an overlapping edit of its own.
+            let mut covered = 0;
 
             for (i, &b) in seg.iter().enumerate() {
-                if b != b'=' || !is_assignment_eq(seg, i) {
+                if i < covered || b != b'=' || !is_assignment_eq(seg, i) {
                     continue;
                 }
 
@@ -265,6 +271,7 @@ impl Rule for PipeParamDefaultParens {
                 if delim <= start {
                     continue;
                 }
+                covered = delim;
 
                 // trim trailing spaces before delim
                 let mut end = delim;
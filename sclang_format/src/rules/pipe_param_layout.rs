@@ -1,5 +1,5 @@
 use crate::engine::{Ctx, TextEdit};
-use crate::rules::Rule;
+use crate::rules::{Category, Example, Rule};
 use anyhow::Result;
 
 const fn is_space(b: u8) -> bool {
@@ -22,6 +22,21 @@ impl Rule for PipeParamOnBraceLine {
         "pipe_param_on_brace_line"
     }
 
+    fn description(&self) -> &'static str {
+        "Keep a `|...|` parameter list on the line of its opening brace."
+    }
+
+    fn category(&self) -> Category {
+        Category::Structural
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "f = {\n|a, b|\n    a + b\n};\n",
+            after: "f = { |a, b|\n    a + b\n};\n",
+        }
+    }
+
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
         let bytes = cx.bytes();
         let len = bytes.len();
@@ -1,5 +1,5 @@
 use crate::engine::{Ctx, TextEdit};
-use crate::rules::Rule;
+use crate::rules::{Category, Example, Rule};
 use anyhow::{Result, Ok};
 use tree_sitter::{Query, QueryCursor, StreamingIterator};
 
@@ -7,7 +7,22 @@ pub struct NoSpaceBeforeSemicolon;
 
 impl Rule for NoSpaceBeforeSemicolon {
     fn name(&self) -> &'static str {
-        "NoSpaceBeforeSemicolon"
+        "no_space_before_semicolon"
+    }
+
+    fn description(&self) -> &'static str {
+        "No whitespace before `;`."
+    }
+
+    fn category(&self) -> Category {
+        Category::Spacing
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "x = 1 ;\n",
+            after: "x = 1;\n",
+        }
     }
 
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
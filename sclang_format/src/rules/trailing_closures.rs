@@ -1,7 +1,6 @@
 use crate::engine::{Ctx, TextEdit};
-use crate::rules::Rule;
+use crate::rules::{Category, Example, Rule};
 use anyhow::Result;
-use tree_sitter::Node;
 
 const fn is_space(b: u8) -> bool {
     b == b' ' || b == b'\t' || b == b'\r' || b == b'\n'
@@ -11,19 +10,6 @@ const fn is_ident_char(b: u8) -> bool {
     b.is_ascii_alphanumeric() || b == b'_' || b == b'\\'
 }
 
-/// AST-based “are we in a string/comment?”
-fn in_comment_or_string(root: &Node, byte: usize) -> bool {
-    let mut cur = root.descendant_for_byte_range(byte, byte + 1);
-    while let Some(n) = cur {
-        match n.kind() {
-            "comment" | "block_comment" | "line_comment" | "string" => return true,
-            _ => {}
-        }
-        cur = n.parent();
-    }
-    false
-}
-
 /// Try to rewrite an `if(cond, { ... }, { ... })` or `if(cond, { ... })` call at `start_if`.
 /// Returns (`start_byte`, `end_byte`, replacement) if successful.
 fn rewrite_if_call(buf: &[u8], start_if: usize) -> Option<(usize, usize, String)> {
@@ -312,10 +298,25 @@ impl Rule for TrailingClosures {
         "trailing_closures"
     }
 
+    fn description(&self) -> &'static str {
+        "Turn `if(cond, { ... }, { ... })` into `if (cond) { ... } { ... }`."
+    }
+
+    fn category(&self) -> Category {
+        Category::Semantic
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "if(x > 0, { 1 }, { 2 });\n",
+            after: "if (x > 0) { 1 } { 2 };\n",
+        }
+    }
+
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
         let bytes = cx.bytes();
         let buf: &[u8] = &bytes;
-        let root = cx.tree.root_node();
+        let classes = cx.classes();
         let len = buf.len();
 
         let mut edits: Vec<TextEdit> = Vec::new();
@@ -323,7 +324,7 @@ impl Rule for TrailingClosures {
 
         while i < len {
             // First, try `if(...)` form.
-            if i + 2 <= len && &buf[i..i + 2] == b"if" && !in_comment_or_string(&root, i)
+            if i + 2 <= len && &buf[i..i + 2] == b"if" && classes.is_code(i)
                 && let Some((start, end, repl)) = rewrite_if_call(buf, i) {
                     edits.push(TextEdit {
                         start_byte: start,
@@ -336,7 +337,7 @@ impl Rule for TrailingClosures {
                 }
 
             // Then, try `.do({ ... })` form.
-            if buf[i] == b'.' && !in_comment_or_string(&root, i)
+            if buf[i] == b'.' && classes.is_code(i)
                 && let Some((start, end, repl)) = rewrite_do_call(buf, i) {
                     edits.push(TextEdit {
                         start_byte: start,
@@ -1,4 +1,5 @@
 use crate::engine::{Ctx, TextEdit};
+use crate::rules::{Category, Example};
 use anyhow::Result;
 
 pub struct TrimTrailingWhitespaceAndEofNewline;
@@ -8,6 +9,21 @@ impl crate::rules::Rule for TrimTrailingWhitespaceAndEofNewline {
         "trailing_whitespace"
     }
 
+    fn description(&self) -> &'static str {
+        "Trim trailing whitespace and end the file with exactly one newline."
+    }
+
+    fn category(&self) -> Category {
+        Category::Cleanup
+    }
+
+    fn example(&self) -> Example {
+        Example {
+            before: "x = 1;   \n\n\n",
+            after: "x = 1;\n",
+        }
+    }
+
     fn run(&self, cx: &mut Ctx) -> Result<usize> {
         let bytes = cx.bytes();
         let b = bytes.as_slice();
@@ -0,0 +1,253 @@
+// src/verify.rs
+//
+// `--verify`: make sure a rule only changed layout. The text before and after
+// each rule is reduced to its token stream, the rewrites the semantic rules
+// are allowed to make are folded into one canonical spelling, and the two
+// streams are compared.
+
+use crate::engine::{Ctx, lex};
+use std::fmt;
+
+#[derive(Clone, Debug)]
+struct Token {
+    text: String,
+    /// Byte offset in the text the token was read from.
+    at: usize,
+}
+
+impl Token {
+    fn is(&self, text: &str) -> bool {
+        self.text == text
+    }
+}
+
+/// Tokens of one version of the buffer: code and comments separately, since
+/// the closure rewrites may move a comment across a comma or parenthesis.
+pub struct Snapshot {
+    src: String,
+    code: Vec<Token>,
+    comments: Vec<Token>,
+}
+
+impl Snapshot {
+    #[must_use]
+    pub fn of(cx: &Ctx) -> Self {
+        let src = cx.to_string();
+        let (mut code, mut comments) = (Vec::new(), Vec::new());
+        for (range, class) in lex::tokens(cx.tree.root_node(), &src, cx.classes()) {
+            let at = range.start;
+            let text = &src[range];
+            if class.is_comment() {
+                // Indentation and spacing rules may reflow comment text.
+                comments.push(Token {
+                    text: text.split_whitespace().collect(),
+                    at,
+                });
+            } else {
+                code.push(Token {
+                    text: text.to_string(),
+                    at,
+                });
+            }
+        }
+        Self {
+            code: canonical(code),
+            comments,
+            src,
+        }
+    }
+
+    /// 1-based line and column (in characters) of byte `at`.
+    fn position(&self, at: usize) -> (usize, usize) {
+        let at = at.min(self.src.len());
+        let before = &self.src[..at];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (
+            before.matches('\n').count() + 1,
+            before[line_start..].chars().count() + 1,
+        )
+    }
+}
+
+/// Spell every rewrite a semantic rule may make the same way on both sides:
+///
+/// - `{ arg a, b; ...` becomes `{ |a, b| ...` (`arg_to_pipe_params`);
+/// - commas and parentheses inside `|...|` go (`pipe_param_missing_commas`,
+///   `pipe_param_default_parens`);
+/// - a `;` right before `}` goes (`no_final_semicolon_before_brace`);
+/// - trailing closures move back inside the call's parentheses
+///   (`trailing_closures`, `extra_trailing_closures`).
+fn canonical(mut tokens: Vec<Token>) -> Vec<Token> {
+    for i in 1..tokens.len() {
+        if tokens[i].is("arg")
+            && tokens[i - 1].is("{")
+            && let Some(end) = statement_end(&tokens, i)
+        {
+            tokens[i].text = "|".to_string();
+            tokens[end].text = "|".to_string();
+        }
+    }
+
+    let mut out: Vec<Token> = Vec::with_capacity(tokens.len());
+    let mut in_params = false;
+    for (i, t) in tokens.iter().enumerate() {
+        let prev_is_brace = out.last().is_some_and(|p| p.is("{"));
+        if t.is("|") {
+            in_params = !in_params && prev_is_brace;
+        } else if in_params && (t.is(",") || t.is("(") || t.is(")")) {
+            continue;
+        }
+        if t.is(";") && tokens.get(i + 1).is_some_and(|n| n.is("}")) {
+            continue;
+        }
+        out.push(t.clone());
+    }
+    inline_closures(&out)
+}
+
+/// Index of the `;` ending the statement that starts at `start`.
+fn statement_end(tokens: &[Token], start: usize) -> Option<usize> {
+    let mut depth = 0i32;
+    for (i, t) in tokens.iter().enumerate().skip(start) {
+        match t.text.as_str() {
+            "(" | "[" | "{" => depth += 1,
+            ")" | "]" | "}" if depth == 0 => return None,
+            ")" | "]" | "}" => depth -= 1,
+            ";" if depth == 0 => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Rewrite `f(a) { b }` and `f { b }` as `f(a, { b })` and `f({ b })`.
+fn inline_closures(tokens: &[Token]) -> Vec<Token> {
+    let mut out: Vec<Token> = Vec::with_capacity(tokens.len());
+    let mut i = 0;
+    while i < tokens.len() {
+        if !tokens[i].is("{") {
+            out.push(tokens[i].clone());
+            i += 1;
+            continue;
+        }
+        let end = matching_brace(tokens, i);
+        let mut block = vec![tokens[i].clone()];
+        block.extend(inline_closures(&tokens[i + 1..end]));
+        block.extend(tokens.get(end).cloned());
+        i = end + 1;
+
+        let synth = |text: &str, at: usize| Token {
+            text: text.to_string(),
+            at,
+        };
+        let at = block[0].at;
+        match out.last() {
+            Some(p) if p.is(")") => {
+                let close = out.pop().unwrap_or_else(|| synth(")", at));
+                if !out.last().is_some_and(|p| p.is("(")) {
+                    out.push(synth(",", at));
+                }
+                out.extend(block);
+                out.push(close);
+            }
+            Some(p) if is_callee(&p.text) => {
+                out.push(synth("(", at));
+                out.extend(block);
+                out.push(synth(")", at));
+            }
+            _ => out.extend(block),
+        }
+    }
+    out
+}
+
+/// Index of the `}` closing the `{` at `open`, or the end of `tokens`.
+fn matching_brace(tokens: &[Token], open: usize) -> usize {
+    let mut depth = 0usize;
+    for (i, t) in tokens.iter().enumerate().skip(open) {
+        if t.is("{") {
+            depth += 1;
+        } else if t.is("}") {
+            depth -= 1;
+            if depth == 0 {
+                return i;
+            }
+        }
+    }
+    tokens.len()
+}
+
+fn is_callee(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_alphanumeric() || c == '_')
+        && !matches!(text, "var" | "arg" | "classvar" | "const")
+}
+
+/// A rule changed more than layout.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifyError {
+    /// `Rule::name()` of the offending rule, if known.
+    pub rule: Option<&'static str>,
+    /// 1-based position in the rule's input of the first difference.
+    pub line: usize,
+    pub column: usize,
+    /// Token the rule's input had there; `None` at the end of the input.
+    pub expected: Option<String>,
+    /// Token the rule's output has instead.
+    pub found: Option<String>,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |t: &Option<String>| {
+            t.as_ref()
+                .map_or_else(|| "end of input".to_string(), |t| format!("`{t}`"))
+        };
+        match self.rule {
+            Some(rule) => write!(f, "rule `{rule}` changed the program")?,
+            None => write!(f, "formatting changed the program")?,
+        }
+        write!(
+            f,
+            " at {}:{}: expected {}, found {}",
+            self.line,
+            self.column,
+            show(&self.expected),
+            show(&self.found)
+        )
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Check that `after` has the same tokens as `before`, up to layout and the
+/// rewrites listed on [`canonical`].
+///
+/// # Errors
+/// Fails with the first difference, located in `before`.
+pub fn compare(
+    before: &Snapshot,
+    after: &Snapshot,
+    rule: Option<&'static str>,
+) -> Result<(), VerifyError> {
+    for (b, a) in [
+        (&before.code, &after.code),
+        (&before.comments, &after.comments),
+    ] {
+        let Some(i) = (0..b.len().max(a.len()))
+            .find(|&i| b.get(i).map(|t| &t.text) != a.get(i).map(|t| &t.text))
+        else {
+            continue;
+        };
+        let at = b.get(i).map_or(before.src.len(), |t| t.at);
+        let (line, column) = before.position(at);
+        return Err(VerifyError {
+            rule,
+            line,
+            column,
+            expected: b.get(i).map(|t| t.text.clone()),
+            found: a.get(i).map(|t| t.text.clone()),
+        });
+    }
+    Ok(())
+}
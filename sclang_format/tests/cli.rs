@@ -0,0 +1,273 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+mod common;
+use common::scratch;
+
+fn sclang_format() -> Command {
+    Command::new(assert_cmd::cargo::cargo_bin!("sclang_format"))
+}
+
+#[test]
+fn check_passes_on_formatted_input() {
+    sclang_format()
+        .arg("--check")
+        .write_stdin("x = 1;\n")
+        .assert()
+        .code(0)
+        .stdout("");
+}
+
+#[test]
+fn check_lists_files_that_would_change_and_leaves_them_alone() {
+    let dir = scratch("check_dirty");
+    let file = dir.join("dirty.scd");
+    fs::write(&file, "x=1;").unwrap();
+
+    sclang_format()
+        .arg("--check")
+        .arg(&file)
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("dirty.scd"));
+    assert_eq!(fs::read_to_string(&file).unwrap(), "x=1;");
+}
+
+#[test]
+fn check_reports_io_errors_with_their_own_status() {
+    sclang_format()
+        .args(["--check", "does/not/exist.scd"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn diff_composes_with_check() {
+    sclang_format()
+        .args(["--check", "--diff", "--color", "never"])
+        .write_stdin("x=1;\n")
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("--- <stdin>\t(original)"))
+        .stdout(predicate::str::contains("-x=1;"))
+        .stdout(predicate::str::contains("+x = 1;"))
+        .stdout(predicate::str::contains("would reformat: <stdin>"));
+}
+
+#[test]
+fn directories_are_walked_for_scd_and_sc_files() {
+    let dir = scratch("walk_dirs");
+    fs::create_dir_all(dir.join("nested")).unwrap();
+    fs::write(dir.join("a.scd"), "x=1;").unwrap();
+    fs::write(dir.join("nested").join("B.sc"), "y=2;").unwrap();
+    fs::write(dir.join("notes.txt"), "z=3;").unwrap();
+
+    sclang_format()
+        .arg("--check")
+        .arg(&dir)
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("a.scd"))
+        .stdout(predicate::str::contains("B.sc"))
+        .stdout(predicate::str::contains("notes.txt").not());
+}
+
+#[test]
+fn one_bad_path_does_not_abort_the_others() {
+    let dir = scratch("bad_path");
+    let good = dir.join("good.scd");
+    fs::write(&good, "x=1;").unwrap();
+
+    sclang_format()
+        .arg("--write")
+        .arg(dir.join("missing.scd"))
+        .arg(&good)
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("missing.scd"));
+    assert_eq!(fs::read_to_string(&good).unwrap(), "x = 1;\n");
+}
+
+#[test]
+fn unmatched_glob_is_reported() {
+    let dir = scratch("empty_glob");
+    sclang_format()
+        .arg("--check")
+        .arg(dir.join("*.scd"))
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("no files match"));
+}
+
+#[test]
+fn parallel_runs_report_in_input_order() {
+    let dir = scratch("parallel_order");
+    let mut expected = String::new();
+    for i in 0..12 {
+        let file = dir.join(format!("f{i:02}.scd"));
+        fs::write(&file, format!("x{i}={i};")).unwrap();
+        expected.push_str(&format!("would reformat: {}\n", file.display()));
+    }
+
+    for jobs in ["1", "4"] {
+        sclang_format()
+            .args(["--check", "--jobs", jobs])
+            .arg(&dir)
+            .assert()
+            .code(1)
+            .stdout(expected.clone());
+    }
+}
+
+#[test]
+fn line_range_only_touches_the_selected_lines() {
+    sclang_format()
+        .args(["--lines", "2:2"])
+        .write_stdin("a=1;\nb=2;\nc=3;\n")
+        .assert()
+        .success()
+        .stdout("a=1;\nb = 2;\nc=3;\n");
+}
+
+#[test]
+fn line_ranges_start_at_line_one() {
+    sclang_format()
+        .args(["--lines", "0:2"])
+        .write_stdin("a=1;\n")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("lines are numbered from 1"));
+}
+
+#[test]
+fn only_runs_the_named_rules() {
+    sclang_format()
+        .args(["--only", "trailing_whitespace"])
+        .write_stdin("a=1;   ")
+        .assert()
+        .success()
+        .stdout("a=1;\n");
+}
+
+#[test]
+fn unknown_rule_names_are_rejected() {
+    sclang_format()
+        .args(["--disable", "no_such_rule"])
+        .write_stdin("a = 1;\n")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("unknown rule `no_such_rule`"));
+}
+
+#[test]
+fn list_rules_prints_the_catalog_in_pipeline_order() {
+    let out = sclang_format().arg("--list-rules").assert().success();
+    let stdout = String::from_utf8(out.get_output().stdout.clone()).unwrap();
+    let listed: Vec<&str> = stdout
+        .lines()
+        .filter_map(|l| l.split_whitespace().next())
+        .collect();
+    assert_eq!(listed, sclang_format::rules::names());
+}
+
+#[test]
+fn explain_shows_category_and_example() {
+    sclang_format()
+        .args(["--explain", "arg_to_pipe_params"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("category: semantic"))
+        .stdout(predicate::str::contains("arg a, b;"))
+        .stdout(predicate::str::contains("f = { |a, b|"));
+
+    sclang_format()
+        .args(["--explain", "no_such_rule"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("unknown rule `no_such_rule`"));
+}
+
+#[test]
+fn syntax_errors_are_reported_and_block_formatting() {
+    sclang_format()
+        .write_stdin("a=1;\nx = (1 + ;\n")
+        .assert()
+        .code(3)
+        .stdout("")
+        .stderr(predicate::str::contains("<stdin>:2:"))
+        .stderr(predicate::str::contains("2 | x = (1 + ;"))
+        .stderr(predicate::str::contains("^"));
+}
+
+#[test]
+fn allow_errors_formats_anyway() {
+    sclang_format()
+        .args(["--allow-errors", "--only", "spaces_around_assignment"])
+        .write_stdin("a=1;\nx = (1 + ;\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("a = 1;\n"));
+}
+
+#[test]
+fn skip_errors_formats_the_statements_that_parse() {
+    sclang_format()
+        .args(["--skip-errors", "--only", "spaces_around_assignment"])
+        .write_stdin("a=1;\nx = (1 + ;\n")
+        .assert()
+        .success()
+        .stdout("a = 1;\nx = (1 + ;\n")
+        .stderr(predicate::str::contains("<stdin>: skipped line 2"));
+}
+
+#[test]
+fn verify_accepts_semantic_rewrites() {
+    sclang_format()
+        .args(["--verify", "--only", "trailing_closures"])
+        .write_stdin("if(x > 0, { 1 }, { 2 });\n")
+        .assert()
+        .success()
+        .stdout("if (x > 0) { 1 } { 2 };\n");
+}
+
+#[test]
+fn engine_doc_prints_with_the_pretty_printer() {
+    sclang_format()
+        .args(["--engine", "doc", "--phase", "inline", "--max-width", "12"])
+        .write_stdin("x=[100,200,300];\n")
+        .assert()
+        .success()
+        .stdout("x = [\n    100,\n    200,\n    300\n];\n");
+}
+
+#[test]
+fn indent_strategy_ast_reindents_from_nesting() {
+    let args = ["--phase", "inline", "--only", "indent_style"];
+    let src = "{\nx = 1;\n      y = 2;\n}\n";
+    sclang_format()
+        .args(args)
+        .write_stdin(src)
+        .assert()
+        .success()
+        .stdout(src);
+    sclang_format()
+        .args(args)
+        .args(["--indent-strategy", "ast"])
+        .write_stdin(src)
+        .assert()
+        .success()
+        .stdout("{\n    x = 1;\n    y = 2;\n}\n");
+}
+
+#[test]
+fn directives_naming_unknown_rules_are_warned_about() {
+    sclang_format()
+        .args(["--phase", "inline", "--only", "spaces_around_assignment"])
+        .write_stdin("// sclang-format-disable-next-line no_such_rule\nx=1;\n")
+        .assert()
+        .success()
+        .stdout("// sclang-format-disable-next-line no_such_rule\nx = 1;\n")
+        .stderr(predicate::str::contains(
+            "<stdin>: warning: line 1: unknown rule `no_such_rule`",
+        ));
+}
@@ -1,10 +1,17 @@
+// Shared by several test crates; not every crate uses every helper.
+#![allow(dead_code)]
+
 use anyhow::Result;
+use sclang_format::engine::{Ctx, TextEdit};
+use sclang_format::{IndentStyle, grammar};
 use std::io::Write;
 use std::process::{Command, Stdio};
 
 pub fn run_cli_on_str(input: &str) -> Result<String> {
     // ✅ macro form (not deprecated)
     let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("sclang_format"));
+    // Several fixtures are deliberately malformed (missing commas, `else`).
+    cmd.arg("--allow-errors");
     cmd.stdin(Stdio::piped()).stdout(Stdio::piped());
 
     let mut child = cmd.spawn()?;
@@ -14,3 +21,29 @@ pub fn run_cli_on_str(input: &str) -> Result<String> {
     anyhow::ensure!(out.status.success(), "formatter non-zero: {}", out.status);
     Ok(String::from_utf8(out.stdout)?)
 }
+
+/// Fresh scratch directory under the system temp dir.
+pub fn scratch(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("sclang_format_{name}_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// A context over `src` with a 4-space indent.
+pub fn ctx(src: &str) -> Ctx {
+    Ctx::new(
+        src.to_string(),
+        grammar::language(),
+        IndentStyle::Spaces { width: 4 },
+    )
+    .unwrap()
+}
+
+pub fn edit(start_byte: usize, end_byte: usize, replacement: &str) -> TextEdit {
+    TextEdit {
+        start_byte,
+        end_byte,
+        replacement: replacement.to_string(),
+    }
+}
@@ -0,0 +1,99 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+mod common;
+use common::scratch;
+
+#[test]
+fn config_is_discovered_from_ancestor_directory() {
+    let root = scratch("config_discovery");
+    fs::write(
+        root.join("sclang-format.toml"),
+        "max_width = 100\n[indent]\nstyle = \"tabs\"\n",
+    )
+    .unwrap();
+    let nested = root.join("a").join("b");
+    fs::create_dir_all(&nested).unwrap();
+    let file = nested.join("x.scd");
+    fs::write(&file, "x = 1;\n").unwrap();
+
+    Command::new(assert_cmd::cargo::cargo_bin!("sclang_format"))
+        .arg("--print-config")
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("max_width = 100"))
+        .stdout(predicate::str::contains("style = \"tabs\""));
+}
+
+#[test]
+fn cli_flags_override_config_values() {
+    let root = scratch("config_override");
+    fs::write(
+        root.join("sclang-format.toml"),
+        "phase = \"inline\"\n[indent]\nstyle = \"spaces\"\nwidth = 2\n",
+    )
+    .unwrap();
+
+    Command::new(assert_cmd::cargo::cargo_bin!("sclang_format"))
+        .current_dir(&root)
+        .args(["--print-config", "--indent-width", "8"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("phase = \"inline\""))
+        .stdout(predicate::str::contains("width = 8"));
+}
+
+#[test]
+fn unknown_config_keys_are_rejected() {
+    let root = scratch("config_unknown_key");
+    fs::write(root.join("sclang-format.toml"), "line_width = 100\n").unwrap();
+
+    Command::new(assert_cmd::cargo::cargo_bin!("sclang_format"))
+        .current_dir(&root)
+        .arg("--print-config")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("line_width"));
+}
+
+#[test]
+fn line_ranges_resolve_to_whole_lines() {
+    use sclang_format::FormatRange;
+    let src = "a\nbb\nccc\n";
+    assert_eq!(FormatRange::Lines { start: 2, end: 2 }.to_bytes(src), 2..5);
+    assert_eq!(
+        FormatRange::Lines { start: 1, end: 9 }.to_bytes(src),
+        0..src.len()
+    );
+    // `--lines 2:18446744073709551615` runs to the end of the file.
+    assert_eq!(
+        FormatRange::Lines {
+            start: 2,
+            end: usize::MAX
+        }
+        .to_bytes(src),
+        2..src.len()
+    );
+    assert_eq!(
+        FormatRange::Bytes { start: 3, end: 99 }.to_bytes(src),
+        3..src.len()
+    );
+}
+
+#[test]
+fn max_width_is_shared_by_width_aware_rules() {
+    use sclang_format::{FormatOptions, Phase, format_source_with_options};
+    let src = "a = [\n    100,\n    200\n];\n";
+    let opts = |max_width| FormatOptions {
+        phase: Phase::Inline,
+        max_width,
+        ..FormatOptions::default()
+    };
+    assert_eq!(
+        format_source_with_options(src, &opts(80)).unwrap(),
+        "a = [100, 200];\n"
+    );
+    // "a = [100, 200];" is 15 columns wide.
+    assert_eq!(format_source_with_options(src, &opts(14)).unwrap(), src);
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use common::ctx;
+use sclang_format::config::RuleSelection;
+use sclang_format::rules::{self, NonConvergence};
+use sclang_format::{Error, FormatOptions, Formatter, Phase, Warning};
+
+fn selection() -> RuleSelection {
+    RuleSelection {
+        only: vec!["spaces_around_assignment".to_string()],
+        ..RuleSelection::default()
+    }
+}
+
+fn opts(max_passes: usize) -> FormatOptions {
+    FormatOptions {
+        phase: Phase::Inline,
+        max_passes,
+        rules: selection(),
+        ..FormatOptions::default()
+    }
+}
+
+#[test]
+fn pipeline_reruns_until_nothing_changes() {
+    let mut formatter = Formatter::new(opts(2)).unwrap();
+    assert_eq!(formatter.format("a=1;\n").unwrap(), "a = 1;\n");
+    assert!(formatter.warnings().is_empty());
+}
+
+#[test]
+fn the_confirming_pass_does_not_count_against_the_limit() {
+    // One pass formats, the next only sees that nothing changes any more.
+    let mut formatter = Formatter::new(opts(1)).unwrap();
+    assert_eq!(formatter.format("a=1;\n").unwrap(), "a = 1;\n");
+    assert!(formatter.warnings().is_empty());
+}
+
+#[test]
+fn hitting_the_pass_limit_keeps_the_output_and_names_the_rules_still_changing() {
+    // No pass may change the buffer, so the checking pass finds work left.
+    // Options reject a limit of 0, so this drives the pipeline directly.
+    let mut cx = ctx("a=1;\n");
+    cx.max_passes = 0;
+    rules::run_inline_with(&mut cx, &selection()).unwrap();
+    assert_eq!(cx.to_string(), "a = 1;\n");
+    assert_eq!(
+        cx.warnings(),
+        [Warning::NonConvergence(NonConvergence {
+            passes: 0,
+            rules: vec!["spaces_around_assignment"],
+        })]
+    );
+    assert_eq!(
+        cx.warnings()[0].to_string(),
+        "formatting did not settle after 0 passes; still changing: spaces_around_assignment"
+    );
+}
+
+#[test]
+fn strict_convergence_turns_the_warning_into_an_error() {
+    let mut cx = ctx("a=1;\n");
+    cx.max_passes = 0;
+    cx.strict_convergence = true;
+    let err = rules::run_inline_with(&mut cx, &selection()).unwrap_err();
+    let Error::NonConvergence(err) = err else {
+        panic!("expected non-convergence, got {err:?}");
+    };
+    assert_eq!(
+        err,
+        NonConvergence {
+            passes: 0,
+            rules: vec!["spaces_around_assignment"],
+        }
+    );
+}
+
+#[test]
+fn a_pass_limit_of_zero_is_rejected_up_front() {
+    // With no pass allowed to change anything, every change would be
+    // reported as not settling.
+    let built = FormatOptions::builder().max_passes(0).build();
+    assert!(matches!(built, Err(Error::InvalidOptions(_))), "{built:?}");
+    let parsed = FormatOptions::from_toml("max_passes = 0\n");
+    assert!(parsed.is_err());
+    assert!(FormatOptions::builder().max_passes(1).build().is_ok());
+}
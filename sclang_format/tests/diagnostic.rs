@@ -0,0 +1,53 @@
+use pretty_assertions::assert_eq;
+use sclang_format::{
+    Error, FormatOptions, Phase, SyntaxError, SyntaxErrors, format_source_with_options,
+};
+
+#[test]
+fn render_underlines_the_error_keeping_tabs() {
+    let err = SyntaxError {
+        bytes: 5..8,
+        line: 12,
+        column: 4,
+        message: "syntax error".to_string(),
+        source_line: "\tx (1 + ;".to_string(),
+    };
+    assert_eq!(
+        err.render("a.scd"),
+        "a.scd:12:4: syntax error\n   |\n12 | \tx (1 + ;\n   | \t  ^^^\n"
+    );
+}
+
+#[test]
+fn render_puts_one_caret_under_each_multibyte_character() {
+    // "→" is three bytes but one column.
+    let err = SyntaxError {
+        bytes: 4..7,
+        line: 1,
+        column: 5,
+        message: "syntax error".to_string(),
+        source_line: "x = → 1;".to_string(),
+    };
+    assert_eq!(
+        err.render("a.scd"),
+        "a.scd:1:5: syntax error\n  |\n1 | x = → 1;\n  |     ^\n"
+    );
+}
+
+#[test]
+fn formatting_refuses_unparsable_input_unless_allowed() {
+    let src = "x = (1 + ;\n";
+    let err = format_source_with_options(src, &FormatOptions::default()).unwrap_err();
+    let Error::Syntax(SyntaxErrors(found)) = err else {
+        panic!("expected syntax errors, got {err:?}");
+    };
+    assert!(!found.is_empty());
+    assert!(found.iter().all(|e| e.line == 1));
+
+    let opts = FormatOptions {
+        allow_errors: true,
+        phase: Phase::Pre,
+        ..FormatOptions::default()
+    };
+    assert_eq!(format_source_with_options(src, &opts).unwrap(), src);
+}
@@ -0,0 +1,34 @@
+use sclang_format::diff::unified_diff;
+
+#[test]
+fn identical_text_produces_no_diff() {
+    assert_eq!(unified_diff("x = 1;\n", "x = 1;\n", "a.scd", false), "");
+}
+
+#[test]
+fn plain_diff_has_headers_hunks_and_no_escape_codes() {
+    let out = unified_diff("a\nb\nc\n", "a\nB\nc\n", "a.scd", false);
+    assert_eq!(
+        out,
+        "--- a.scd\t(original)\n+++ a.scd\t(formatted)\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n"
+    );
+}
+
+#[test]
+fn colored_diff_marks_removals_and_additions() {
+    let out = unified_diff("a\n", "b\n", "a.scd", true);
+    assert!(
+        out.contains("\u{1b}[31m-a"),
+        "removal should be red: {out:?}"
+    );
+    assert!(
+        out.contains("\u{1b}[32m+b"),
+        "addition should be green: {out:?}"
+    );
+}
+
+#[test]
+fn missing_final_newline_is_flagged() {
+    let out = unified_diff("x=1;", "x = 1;\n", "a.scd", false);
+    assert!(out.contains("-x=1;\n\\ No newline at end of file\n+x = 1;\n"));
+}
@@ -0,0 +1,115 @@
+use pretty_assertions::assert_eq;
+use sclang_format::engine::ClassMap;
+use sclang_format::{FormatOptions, Formatter, Phase, directives, format_source_with_options};
+
+fn spacing(src: &str) -> String {
+    let opts = FormatOptions::builder()
+        .phase(Phase::Inline)
+        .only(["spaces_around_assignment"])
+        .build()
+        .unwrap();
+    format_source_with_options(src, &opts).unwrap()
+}
+
+fn off_regions(src: &str) -> Vec<std::ops::Range<usize>> {
+    directives::off_regions(src, &ClassMap::scan(src.as_bytes()))
+}
+
+#[test]
+fn off_regions_run_from_the_off_comment_to_the_end_of_the_on_comment() {
+    let src = "a = 1;\n// sclang-format off\nb = 2;\n// sclang-format on\nc = 3;\n";
+    let start = src.find("// sclang-format off").unwrap();
+    let end = src.find("c = 3").unwrap() - 1;
+    assert_eq!(off_regions(src), [start..end]);
+}
+
+#[test]
+fn an_unterminated_region_runs_to_the_end_and_stray_directives_do_nothing() {
+    let src = "// sclang-format on\na = 1;\n// sclang-format off\nb = 2;\n";
+    let start = src.find("// sclang-format off").unwrap();
+    assert_eq!(off_regions(src), [start..src.len()]);
+
+    // Directives in strings, or with other words, are not directives.
+    let src = "x = \"// sclang-format off\";\n// sclang-format offline\n";
+    assert!(off_regions(src).is_empty());
+}
+
+#[test]
+fn code_in_an_off_region_is_left_exactly_as_written() {
+    let src = "a=1;\n// sclang-format off\nb=2;\n// sclang-format on\nc=3;\n";
+    assert_eq!(
+        spacing(src),
+        "a = 1;\n// sclang-format off\nb=2;\n// sclang-format on\nc = 3;\n"
+    );
+}
+
+#[test]
+fn the_pre_phase_leaves_off_regions_alone_too() {
+    let src = "a = 1;\r\n// sclang-format off\r\nb = 2;\r\n// sclang-format on\r\n";
+    let opts = FormatOptions::builder().phase(Phase::Pre).build().unwrap();
+    assert_eq!(
+        format_source_with_options(src, &opts).unwrap(),
+        "a = 1;\n// sclang-format off\r\nb = 2;\r\n// sclang-format on\r\n"
+    );
+}
+
+fn inline(src: &str, rules: &[&str]) -> (String, Vec<String>) {
+    let opts = FormatOptions::builder()
+        .phase(Phase::Inline)
+        .only(rules.iter().copied())
+        .build()
+        .unwrap();
+    let mut formatter = Formatter::new(opts).unwrap();
+    let out = formatter.format(src).unwrap();
+    let warnings = formatter
+        .warnings()
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    (out, warnings)
+}
+
+#[test]
+fn ignore_next_line_leaves_just_that_line_alone() {
+    let src = "a=1;\n// sclang-format-ignore-next-line\nb=2;\nc=3;\n";
+    assert_eq!(
+        spacing(src),
+        "a = 1;\n// sclang-format-ignore-next-line\nb=2;\nc = 3;\n"
+    );
+}
+
+#[test]
+fn disable_next_line_only_keeps_the_named_rules_off_it() {
+    let src = "// sclang-format-disable-next-line spaces_around_assignment\nc=f(1,2);\nd=f(1,2);\n";
+    let (out, warnings) = inline(src, &["spaces_around_assignment", "spaces_after_commas"]);
+    assert_eq!(
+        out,
+        "// sclang-format-disable-next-line spaces_around_assignment\nc=f(1, 2);\nd = f(1, 2);\n"
+    );
+    assert!(warnings.is_empty(), "{warnings:?}");
+}
+
+#[test]
+fn unknown_rules_in_directives_are_warned_about() {
+    let src =
+        "x=1;\n// sclang-format-disable-next-line spaces_around_assignment,no_such_rule\ny=2;\n";
+    let (out, warnings) = inline(src, &["spaces_around_assignment"]);
+    assert_eq!(
+        out,
+        "x = 1;\n// sclang-format-disable-next-line spaces_around_assignment,no_such_rule\ny=2;\n"
+    );
+    assert_eq!(
+        warnings,
+        ["line 2: unknown rule `no_such_rule` in `sclang-format-disable-next-line`"]
+    );
+}
+
+#[test]
+fn next_line_directives_list_their_rules() {
+    let src = "// sclang-format-disable-next-line trailing_closures, dot_chain_layout\nx;\n";
+    let [d] = directives::next_line(src, &ClassMap::scan(src.as_bytes()))
+        .try_into()
+        .unwrap();
+    assert_eq!(d.line, 1);
+    assert_eq!(d.rules, ["trailing_closures", "dot_chain_layout"]);
+}
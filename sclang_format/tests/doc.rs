@@ -0,0 +1,96 @@
+use pretty_assertions::assert_eq;
+use sclang_format::IndentStyle;
+use sclang_format::doc::{Doc, RenderConfig, d, join, render};
+
+fn cfg(width: usize) -> RenderConfig {
+    RenderConfig {
+        width,
+        indent_style: IndentStyle::Spaces { width: 4 },
+    }
+}
+
+/// `[a, b, c]`, or one element per line when it does not fit.
+fn array(items: &[&str]) -> Doc {
+    d::cat(vec![
+        d::txt("["),
+        d::cat(vec![
+            d::sb(),
+            join(
+                &d::cat(vec![d::txt(","), d::sl()]),
+                items.iter().map(|&i| d::txt(i)).collect(),
+            ),
+        ])
+        .indent(),
+        d::sb(),
+        d::txt("]"),
+    ])
+    .group()
+}
+
+#[test]
+fn group_stays_flat_when_it_fits() {
+    let doc = d::cat(vec![d::txt("a = "), array(&["1", "2", "3"]), d::txt(";")]);
+    assert_eq!(render(&doc, &cfg(14)), "a = [1, 2, 3];");
+}
+
+#[test]
+fn group_breaks_when_it_does_not_fit_including_what_follows() {
+    // Without the trailing `;` it would fit in 13 columns.
+    let doc = d::cat(vec![d::txt("a = "), array(&["1", "2", "3"]), d::txt(";")]);
+    assert_eq!(render(&doc, &cfg(13)), "a = [\n    1,\n    2,\n    3\n];");
+}
+
+#[test]
+fn outer_group_breaks_before_inner_ones() {
+    let doc = array(&["100", "200"]);
+    let outer = d::cat(vec![
+        d::txt("f("),
+        d::cat(vec![d::sb(), doc.clone(), d::txt(","), d::sl(), doc]).indent(),
+        d::sb(),
+        d::txt(")"),
+    ])
+    .group();
+    assert_eq!(
+        render(&outer, &cfg(20)),
+        "f(\n    [100, 200],\n    [100, 200]\n)"
+    );
+}
+
+#[test]
+fn hard_line_forces_the_enclosing_group_to_break() {
+    let doc = d::cat(vec![
+        d::txt("{"),
+        d::cat(vec![d::sl(), d::txt("a;"), d::ln(), d::txt("b")]).indent(),
+        d::sl(),
+        d::txt("}"),
+    ])
+    .group();
+    assert_eq!(render(&doc, &cfg(80)), "{\n    a;\n    b\n}");
+}
+
+#[test]
+fn tabs_follow_the_indent_style_and_blank_lines_stay_empty() {
+    let doc = d::cat(vec![
+        d::txt("("),
+        d::cat(vec![d::ln(), d::txt("a"), d::ln(), d::ln(), Doc::text("b")]).indent(),
+        d::ln(),
+        d::txt(")"),
+    ]);
+    let tabs = RenderConfig {
+        width: 80,
+        indent_style: IndentStyle::Tabs,
+    };
+    assert_eq!(render(&doc, &tabs), "(\n\ta\n\n\tb\n)");
+}
+
+#[test]
+fn multi_line_text_does_not_fit_flat() {
+    let doc = d::cat(vec![
+        d::txt("f("),
+        d::cat(vec![d::sb(), d::txt("/* a\nb */ 1")]).indent(),
+        d::sb(),
+        d::txt(")"),
+    ])
+    .group();
+    assert_eq!(render(&doc, &cfg(80)), "f(\n    /* a\nb */ 1\n)");
+}
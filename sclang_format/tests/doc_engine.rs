@@ -0,0 +1,77 @@
+use glob::glob;
+use sclang_format::{Engine, FormatOptions, Phase, format_source_with_options};
+use std::fs;
+
+fn opts(max_width: usize) -> FormatOptions {
+    FormatOptions {
+        phase: Phase::Inline,
+        engine: Engine::Doc,
+        max_width,
+        // Several fixtures are deliberately malformed.
+        allow_errors: true,
+        verify: true,
+        ..FormatOptions::default()
+    }
+}
+
+fn print(src: &str, max_width: usize) -> String {
+    format_source_with_options(src, &opts(max_width)).unwrap()
+}
+
+#[test]
+fn fixtures_reprint_without_changing_tokens_and_settle_at_once() {
+    let mut dirs: Vec<_> = fs::read_dir("tests/fixtures")
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .collect();
+    dirs.sort();
+    for dir in dirs {
+        let input = fs::read_to_string(dir.join("input.scd")).unwrap();
+        let once = format_source_with_options(&input, &opts(80))
+            .unwrap_or_else(|e| panic!("{}: {e:#}", dir.display()));
+        let twice = format_source_with_options(&once, &opts(80)).unwrap();
+        pretty_assertions::assert_eq!(once, twice, "{} is not idempotent", dir.display());
+    }
+}
+
+#[test]
+fn lists_break_one_element_per_line_only_when_too_wide() {
+    let src = "x = [100,200,  300];\n";
+    assert_eq!(print(src, 80), "x = [100, 200, 300];\n");
+    assert_eq!(print(src, 12), "x = [\n    100,\n    200,\n    300\n];\n");
+}
+
+#[test]
+fn a_trailing_function_argument_hugs_the_parentheses() {
+    assert_eq!(
+        print("f.do(1, { a; b });\n", 80),
+        "f.do(1, {\n    a;\n    b\n});\n"
+    );
+}
+
+#[test]
+fn comments_and_single_blank_lines_are_kept() {
+    let src = "a = 1;   // one\n\n\n// two\nb = [1, // three\n2];\n";
+    assert_eq!(
+        print(src, 80),
+        "a = 1;  // one\n\n// two\nb = [\n    1,  // three\n    2\n];\n"
+    );
+}
+
+#[test]
+fn rule_pipeline_output_reprints_without_changing_tokens() {
+    // Whatever `--engine rules` makes of a fixture, `--engine doc` (with
+    // verify on) lays it out again without touching a token.
+    for entry in glob("tests/fixtures/*/input.scd").unwrap() {
+        let input_path = entry.unwrap();
+        let input = fs::read_to_string(&input_path).unwrap();
+        let rules = FormatOptions {
+            engine: Engine::Rules,
+            ..opts(80)
+        };
+        let by_rules = format_source_with_options(&input, &rules)
+            .unwrap_or_else(|e| panic!("{}: {e:#}", input_path.display()));
+        format_source_with_options(&by_rules, &opts(80))
+            .unwrap_or_else(|e| panic!("{}: {e:#}", input_path.display()));
+    }
+}
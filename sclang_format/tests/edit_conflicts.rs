@@ -0,0 +1,85 @@
+use glob::glob;
+use sclang_format::engine::EditConflict;
+use sclang_format::{Error, rules};
+mod common;
+use common::{ctx, edit};
+
+#[test]
+fn identical_edits_are_applied_once() {
+    let mut cx = ctx("x=1;\n");
+    cx.apply_edits(vec![edit(1, 1, " "), edit(1, 1, " "), edit(2, 2, " ")])
+        .unwrap();
+    assert_eq!(cx.to_string(), "x = 1;\n");
+}
+
+#[test]
+fn touching_edits_apply_in_position_order() {
+    // The replacement "B" is emitted first but starts where the insertions
+    // do, so it goes after them; "1" and "2" share a range and keep theirs.
+    let mut cx = ctx("ab;\n");
+    cx.apply_edits(vec![
+        edit(1, 2, "B"),
+        edit(1, 1, "1"),
+        edit(1, 1, "2"),
+        edit(0, 1, "A"),
+    ])
+    .unwrap();
+    assert_eq!(cx.to_string(), "A12B;\n");
+}
+
+#[test]
+fn identical_edits_collapse_even_when_not_adjacent() {
+    // "1", "2", "1" at one offset: the second "1" is the same edit as the
+    // first, so only "1" then "2" are inserted.
+    let mut cx = ctx("ab;\n");
+    cx.apply_edits(vec![edit(1, 1, "1"), edit(1, 1, "2"), edit(1, 1, "1")])
+        .unwrap();
+    assert_eq!(cx.to_string(), "a12b;\n");
+}
+
+#[test]
+fn overlapping_edits_are_rejected_with_the_rule_name() {
+    let mut cx = ctx("x = 1;\n");
+    cx.set_rule(Some("some_rule"));
+    let err = cx
+        .apply_edits(vec![edit(0, 3, "y"), edit(2, 5, "z")])
+        .unwrap_err();
+    let Error::EditConflict(conflict) = &err else {
+        panic!("expected an edit conflict, got {err:?}");
+    };
+    assert_eq!(
+        conflict,
+        &EditConflict {
+            rule: Some("some_rule"),
+            first: 0..3,
+            second: 2..5,
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "rule `some_rule` produced overlapping edits at bytes 0..3 and 2..5"
+    );
+    // Nothing was applied.
+    assert_eq!(cx.to_string(), "x = 1;\n");
+
+    // An insertion strictly inside a replaced span conflicts too.
+    let err = cx
+        .apply_edits(vec![edit(0, 3, "y"), edit(1, 1, "!")])
+        .unwrap_err();
+    assert!(matches!(err, Error::EditConflict(_)));
+}
+
+#[test]
+fn no_rule_produces_conflicting_edits_on_any_fixture() {
+    for entry in glob("tests/fixtures/**/input.scd").unwrap() {
+        let path = entry.unwrap();
+        let input = std::fs::read_to_string(&path).unwrap();
+        for rule in rules::all() {
+            let mut cx = ctx(&input);
+            cx.set_rule(Some(rule.name()));
+            if let Err(e) = rule.run(&mut cx) {
+                panic!("{}: {e:#}", path.display());
+            }
+        }
+    }
+}
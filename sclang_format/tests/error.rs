@@ -0,0 +1,51 @@
+use sclang_format::config::RuleSelection;
+use sclang_format::{
+    Error, FormatOptions, IndentStyle, SyntaxErrors, format_source_with_indent,
+    format_source_with_options,
+};
+
+#[test]
+fn syntax_errors_carry_their_spans() {
+    let src = "x = 1;\ny = (2 + ;\n";
+    let err = format_source_with_indent(src, "all", IndentStyle::Tabs).unwrap_err();
+    let Error::Syntax(SyntaxErrors(found)) = &err else {
+        panic!("expected syntax errors, got {err:?}");
+    };
+    assert!(!found.is_empty());
+    for e in found {
+        assert_eq!(e.line, 2);
+        assert!(e.bytes.start >= src.find('y').unwrap(), "{e:?}");
+    }
+    assert!(err.to_string().starts_with("input does not parse"));
+}
+
+#[test]
+fn bad_options_are_reported_as_such() {
+    let err = format_source_with_indent("x = 1;\n", "inlin", IndentStyle::Tabs).unwrap_err();
+    assert!(matches!(err, Error::InvalidOptions(_)), "{err:?}");
+
+    // Options built by hand skip the builder's check.
+    let opts = FormatOptions {
+        rules: RuleSelection {
+            only: vec!["no_such_rule".to_string()],
+            ..RuleSelection::default()
+        },
+        ..FormatOptions::default()
+    };
+    let err = format_source_with_options("x = 1;\n", &opts).unwrap_err();
+    let Error::InvalidOptions(message) = err else {
+        panic!("expected invalid options, got {err:?}");
+    };
+    assert!(message.contains("unknown rule `no_such_rule`"), "{message}");
+}
+
+#[test]
+fn errors_survive_a_trip_through_anyhow() {
+    let err: anyhow::Error = format_source_with_indent("(", "all", IndentStyle::Tabs)
+        .unwrap_err()
+        .into();
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::Syntax(_))
+    ));
+}
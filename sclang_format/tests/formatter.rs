@@ -0,0 +1,86 @@
+use sclang_format::{
+    Error, FormatOptions, FormatRange, Formatter, IndentStyle, Phase, format_source,
+    format_source_with_indent,
+};
+
+#[test]
+fn unknown_phases_are_an_error_not_a_no_op() {
+    let err = format_source("x = 1;", "inlin").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "unknown phase `inlin` (expected one of pre, inline, post, all)"
+    );
+    assert_eq!(format_source("x = 1;", "post").unwrap(), "x = 1;\n");
+}
+
+#[test]
+fn phases_round_trip_through_their_names() {
+    for phase in [Phase::Pre, Phase::Inline, Phase::Post, Phase::All] {
+        assert_eq!(phase.as_str().parse::<Phase>().unwrap(), phase);
+    }
+    assert!(Phase::All.includes(Phase::Pre));
+    assert!(!Phase::Inline.includes(Phase::Post));
+}
+
+#[test]
+fn builder_sets_options_and_checks_rule_names() {
+    let opts = FormatOptions::builder()
+        .phase(Phase::Inline)
+        .indent_style(IndentStyle::Tabs)
+        .max_width(100)
+        .enable("indent_by_ast_level")
+        .disable("arg_to_pipe_params")
+        .build()
+        .unwrap();
+    assert_eq!(opts.phase, Phase::Inline);
+    assert_eq!(opts.indent_style, IndentStyle::Tabs);
+    assert_eq!(opts.max_width, 100);
+    assert_eq!(opts.rules.enable, ["indent_by_ast_level"]);
+    assert_eq!(opts.rules.disable, ["arg_to_pipe_params"]);
+
+    let err = FormatOptions::builder()
+        .only(["no_such_rule"])
+        .build()
+        .unwrap_err();
+    let Error::InvalidOptions(message) = err else {
+        panic!("expected invalid options, got {err:?}");
+    };
+    assert!(message.contains("unknown rule `no_such_rule`"), "{message}");
+}
+
+#[test]
+fn builder_rejects_backwards_ranges() {
+    for range in [
+        FormatRange::Bytes { start: 5, end: 2 },
+        FormatRange::Lines { start: 3, end: 1 },
+        FormatRange::Lines { start: 0, end: 1 },
+    ] {
+        let err = FormatOptions::builder()
+            .range(Some(range))
+            .build()
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::InvalidOptions(_)),
+            "{range:?}: {err:?}"
+        );
+    }
+    let empty = FormatRange::Bytes { start: 2, end: 2 };
+    assert!(FormatOptions::builder().range(Some(empty)).build().is_ok());
+}
+
+#[test]
+fn a_formatter_can_be_reused_and_reconfigured() {
+    let opts = FormatOptions::builder().phase(Phase::Pre).build().unwrap();
+    let mut formatter = Formatter::new(opts).unwrap();
+    assert_eq!(formatter.format("a = 1;\r\n").unwrap(), "a = 1;\n");
+    assert_eq!(formatter.format("b = 2;\r\n").unwrap(), "b = 2;\n");
+
+    let post = FormatOptions::builder().phase(Phase::Post).build().unwrap();
+    formatter.set_options(post);
+    assert_eq!(formatter.options().phase, Phase::Post);
+    assert_eq!(formatter.format("c = 3;").unwrap(), "c = 3;\n");
+    assert_eq!(
+        format_source_with_indent("c = 3;", "post", IndentStyle::Tabs).unwrap(),
+        "c = 3;\n"
+    );
+}
@@ -0,0 +1,43 @@
+use pretty_assertions::assert_eq;
+use sclang_format::config::RuleSelection;
+use sclang_format::{FormatOptions, format_with_ctx};
+mod common;
+use common::{ctx, edit};
+
+#[test]
+fn edits_touching_a_frozen_span_are_dropped() {
+    let mut cx = ctx("a=1;\nb=2;\nc=3;\n");
+    cx.freeze(5..10);
+    cx.apply_edits(vec![
+        edit(1, 1, " "),
+        edit(6, 6, " "),
+        edit(10, 10, "// "),
+        edit(11, 11, " "),
+    ])
+    .unwrap();
+    assert_eq!(cx.to_string(), "a =1;\nb=2;\nc =3;\n");
+    // The span moved with the edit before it.
+    assert_eq!(cx.frozen(), [6..11]);
+    assert_eq!(cx.slice_bytes(6, 11), "b=2;\n");
+}
+
+#[test]
+fn statements_with_syntax_errors_are_skipped() {
+    let src = "a=1;\nx = (1 + ;\n";
+    let opts = FormatOptions {
+        skip_errors: true,
+        rules: RuleSelection {
+            only: vec!["spaces_around_assignment".to_string()],
+            ..RuleSelection::default()
+        },
+        ..FormatOptions::default()
+    };
+    let mut cx = ctx("");
+    let out = format_with_ctx(&mut cx, src, &opts).unwrap();
+    assert_eq!(out, "a = 1;\nx = (1 + ;\n");
+    let [region] = cx.skipped() else {
+        panic!("expected one skipped region, got {:?}", cx.skipped());
+    };
+    assert_eq!(region.lines.0, 2);
+    assert_eq!(region.bytes.start, 5);
+}
@@ -0,0 +1,71 @@
+use sclang_format::engine::Ctx;
+use sclang_format::grammar;
+use tree_sitter::Parser;
+mod common;
+use common::{ctx, edit};
+
+/// The incrementally updated tree must match a parse from scratch,
+/// positions included.
+fn assert_matches_fresh_parse(cx: &Ctx) {
+    let mut parser = Parser::new();
+    parser.set_language(&grammar::language()).unwrap();
+    let fresh = parser.parse(cx.to_string(), None).unwrap();
+    let (old, new) = (cx.tree.root_node(), fresh.root_node());
+    assert_eq!(old.to_sexp(), new.to_sexp());
+    assert_eq!(old.byte_range(), new.byte_range());
+    assert_eq!(old.end_position(), new.end_position());
+    let mut a = old.walk();
+    let mut b = new.walk();
+    loop {
+        assert_eq!(a.node().start_position(), b.node().start_position());
+        assert_eq!(a.node().end_position(), b.node().end_position());
+        if a.goto_first_child() {
+            assert!(b.goto_first_child());
+            continue;
+        }
+        while !a.goto_next_sibling() {
+            assert!(!b.goto_next_sibling());
+            if !a.goto_parent() {
+                return;
+            }
+            assert!(b.goto_parent());
+        }
+        assert!(b.goto_next_sibling());
+    }
+}
+
+#[test]
+fn edits_on_one_line_keep_the_tree_in_sync() {
+    let mut cx = ctx("x=1;\ny=2;\n");
+    cx.apply_edits(vec![edit(1, 2, " = "), edit(6, 7, " = ")])
+        .unwrap();
+    assert_eq!(cx.to_string(), "x = 1;\ny = 2;\n");
+    assert_matches_fresh_parse(&cx);
+}
+
+#[test]
+fn edits_that_add_and_remove_lines_keep_the_tree_in_sync() {
+    let src = "f = { 1; 2 };\n\n\ng = [3,\n4];\n";
+    let mut cx = ctx(src);
+    let g = src.find('g').unwrap();
+    cx.apply_edits(vec![
+        edit(5, 6, "\n    "),
+        edit(8, 9, "\n    "),
+        edit(g - 2, g, ""),
+        edit(g + 7, g + 8, " "),
+    ])
+    .unwrap();
+    assert_eq!(cx.to_string(), "f = {\n    1;\n    2 };\ng = [3, 4];\n");
+    assert_matches_fresh_parse(&cx);
+}
+
+#[test]
+fn multibyte_text_and_successive_edits_keep_the_tree_in_sync() {
+    let mut cx = ctx("s = \"ünïcödé\";\nt = 1;\n");
+    let t = cx.to_string().find('t').unwrap();
+    cx.apply_edits(vec![edit(t, t, "// ✓\n")]).unwrap();
+    assert_matches_fresh_parse(&cx);
+    cx.apply_edits(vec![edit(0, 1, "longer_name")]).unwrap();
+    assert_matches_fresh_parse(&cx);
+    assert_eq!(cx.to_string(), "longer_name = \"ünïcödé\";\n// ✓\nt = 1;\n");
+}
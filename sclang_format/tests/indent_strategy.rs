@@ -0,0 +1,90 @@
+use glob::glob;
+use pretty_assertions::assert_eq;
+use sclang_format::{
+    FormatOptions, IndentStrategy, IndentStyle, Phase, format_source_with_options,
+};
+
+/// Just the re-indenting: the `ast` strategy on top of `indent_style` alone.
+fn ast_indent(src: &str, indent_style: IndentStyle) -> String {
+    let opts = FormatOptions::builder()
+        .phase(Phase::Inline)
+        .indent_strategy(IndentStrategy::Ast)
+        .indent_style(indent_style)
+        .only(["indent_style"])
+        // Pasted code need not parse; its brackets give the nesting then.
+        .allow_errors(true)
+        .build()
+        .unwrap();
+    format_source_with_options(src, &opts).unwrap()
+}
+
+#[test]
+fn pasted_code_is_reindented_from_its_nesting() {
+    for entry in glob("tests/indent_fixtures/*/input.scd").unwrap() {
+        let input_path = entry.unwrap();
+        let input = std::fs::read_to_string(&input_path).unwrap();
+        let out = ast_indent(&input, IndentStyle::Spaces { width: 4 });
+        assert_eq!(
+            ast_indent(&out, IndentStyle::Spaces { width: 4 }),
+            out,
+            "{} is not idempotent",
+            input_path.display()
+        );
+
+        let rel = input_path.strip_prefix("tests/indent_fixtures").unwrap();
+        let name = rel.parent().unwrap().to_string_lossy().replace('/', "__");
+        insta::with_settings!({
+            snapshot_suffix => "out",
+            input_file => rel,
+        }, {
+            insta::assert_snapshot!(name, out);
+        });
+    }
+}
+
+#[test]
+fn closing_brackets_go_back_to_the_line_that_opened_them() {
+    let src = "f = { |x|\nif (x) {\n[1,\n2]\n} {\n0\n}\n};\n";
+    assert_eq!(
+        ast_indent(src, IndentStyle::Tabs),
+        "f = { |x|\n\tif (x) {\n\t\t[1,\n\t\t\t2]\n\t} {\n\t\t0\n\t}\n};\n"
+    );
+}
+
+#[test]
+fn nesting_comes_from_calls_and_collections_in_the_tree() {
+    let src = "x = f(\n[1, 2],\ng(3,\n4),\n5\n);\ny = a +\nb;\n";
+    assert_eq!(
+        ast_indent(src, IndentStyle::Spaces { width: 2 }),
+        "x = f(\n  [1, 2],\n  g(3,\n    4),\n  5\n);\ny = a +\n  b;\n"
+    );
+}
+
+#[test]
+fn continuation_lines_are_indented_once() {
+    let src = "(\nvar a = 1,\nb = 2;\nx = a +\nb +\n3;\ny = Pdef(\\x)\n.play\n.stop;\n)\n";
+    assert_eq!(
+        ast_indent(src, IndentStyle::Spaces { width: 2 }),
+        "(\nvar a = 1,\n  b = 2;\nx = a +\n  b +\n  3;\ny = Pdef(\\x)\n  .play\n  .stop;\n)\n"
+    );
+}
+
+#[test]
+fn only_the_ast_strategy_runs_the_indentation_rule() {
+    let preserve = FormatOptions::default();
+    assert_eq!(preserve.indent_strategy, IndentStrategy::Preserve);
+    assert!(
+        !preserve
+            .inline_rules()
+            .is_enabled("indent_by_ast_level", false)
+    );
+
+    let ast = FormatOptions::builder()
+        .indent_strategy(IndentStrategy::Ast)
+        .build()
+        .unwrap();
+    assert!(ast.inline_rules().is_enabled("indent_by_ast_level", false));
+
+    let from_toml = FormatOptions::from_toml("indent_strategy = \"ast\"\n").unwrap();
+    assert_eq!(from_toml.indent_strategy, IndentStrategy::Ast);
+}
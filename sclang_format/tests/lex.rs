@@ -0,0 +1,102 @@
+use sclang_format::engine::{ByteClass, ClassMap};
+use sclang_format::rules::{
+    BlockBraceSpacing, CompactShortCollections, KeywordParenSpacing, ParenBracketSpacing, Rule,
+};
+mod common;
+use common::{ctx, edit};
+
+/// Class of each byte of `src`, one letter per byte: c(ode), l(ine comment),
+/// b(lock comment), s(tring), y (symbol), h (char).
+fn classes(src: &str) -> String {
+    let map = ClassMap::scan(src.as_bytes());
+    (0..src.len())
+        .map(|i| match map.class(i) {
+            ByteClass::Code => 'c',
+            ByteClass::LineComment => 'l',
+            ByteClass::BlockComment => 'b',
+            ByteClass::String => 's',
+            ByteClass::Symbol => 'y',
+            ByteClass::Char => 'h',
+        })
+        .collect()
+}
+
+#[test]
+fn strings_honour_escapes() {
+    assert_eq!(classes(r#"x = "a\"b";"#), "ccccssssssc");
+}
+
+#[test]
+fn char_literals_are_not_comments_or_strings() {
+    assert_eq!(classes("$/ / 2"), "hhcccc");
+    assert_eq!(classes("$\" + 1"), "hhcccc");
+    assert_eq!(classes("$\\n;"), "hhhc");
+}
+
+#[test]
+fn symbols_in_both_spellings() {
+    assert_eq!(classes("\\freq, 'a b'"), "yyyyyccyyyyy");
+    // A lone backslash is not a symbol.
+    assert_eq!(classes("a \\ b"), "ccccc");
+}
+
+#[test]
+fn block_comments_nest() {
+    assert_eq!(classes("/* /* */ */x"), "bbbbbbbbbbbc");
+    assert_eq!(classes("// a\nb"), "llllcc");
+}
+
+#[test]
+fn ctx_keeps_classes_in_step_with_edits() {
+    // The old per-rule scanners took `$"` for the start of a string and
+    // never found the closing bracket.
+    let src = "a = [\n    $\",\n    $/\n];\n";
+    let mut cx = ctx(src);
+    assert_eq!(cx.classes().len(), src.len());
+    CompactShortCollections.run(&mut cx).unwrap();
+    assert_eq!(cx.to_string(), "a = [$\", $/];\n");
+    assert_eq!(cx.classes().class(5), ByteClass::Char);
+}
+
+#[test]
+fn byte_scanning_rules_skip_exactly_what_the_class_map_does() {
+    let run = |rule: &dyn Rule, src: &str| {
+        let mut cx = ctx(src);
+        rule.run(&mut cx).unwrap();
+        cx.to_string()
+    };
+    // `$"` is a character, not the start of a string.
+    assert_eq!(
+        run(&BlockBraceSpacing, "x = $\"; a.do{ 1 };\n"),
+        "x = $\"; a.do { 1 };\n"
+    );
+    // The comment only ends at the second `*/`.
+    assert_eq!(
+        run(&ParenBracketSpacing, "/* /* */ f( 1 ) */ g( 1 );\n"),
+        "/* /* */ f( 1 ) */ g(1);\n"
+    );
+    // `\if` is a symbol, and `'` inside one does not open another.
+    assert_eq!(
+        run(&KeywordParenSpacing, "x = [\\if(1), $'];\nif(x) { 1 };\n"),
+        "x = [\\if(1), $'];\nif (x) { 1 };\n"
+    );
+}
+
+#[test]
+fn classes_after_an_edit_match_a_fresh_scan_across_rope_chunks() {
+    // Long enough for the rope to split literals and comments across chunks.
+    let line = "x = \"a // b\"; /* c \"d */ y = \\sym ++ $' ++ 'e f'; // g\n";
+    let src = line.repeat(200);
+    let mut cx = ctx(&src);
+    cx.apply_edits(vec![
+        edit(0, 0, "/* "),
+        edit(src.len() / 2, src.len() / 2, " */"),
+    ])
+    .unwrap();
+    let out = cx.to_string();
+    let fresh = ClassMap::scan(out.as_bytes());
+    assert_eq!(cx.classes().len(), fresh.len());
+    for i in 0..out.len() {
+        assert_eq!(cx.classes().class(i), fresh.class(i), "byte {i}");
+    }
+}
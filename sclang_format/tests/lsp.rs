@@ -0,0 +1,304 @@
+use pretty_assertions::assert_eq;
+use sclang_format::lsp::{read_message, write_message};
+use serde_json::{Value, json};
+use std::fs;
+use std::io::{BufReader, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+mod common;
+use common::scratch;
+
+/// Drives `sclang-format lsp` the way an editor would.
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: u64,
+    /// Notifications received while waiting for a response.
+    notifications: Vec<Value>,
+}
+
+impl Client {
+    fn start() -> Self {
+        let mut child = Command::new(assert_cmd::cargo::cargo_bin!("sclang_format"))
+            .arg("lsp")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Self {
+            child,
+            stdin,
+            stdout,
+            next_id: 1,
+            notifications: Vec::new(),
+        }
+    }
+
+    /// Start a server and initialize it with these workspace folders.
+    fn initialized(roots: &[&Path]) -> Self {
+        let mut client = Self::start();
+        let folders: Vec<Value> = roots
+            .iter()
+            .map(|r| json!({ "uri": uri(r), "name": "ws" }))
+            .collect();
+        let result = client
+            .request(
+                "initialize",
+                json!({ "processId": null, "workspaceFolders": folders }),
+            )
+            .unwrap();
+        assert_eq!(result["capabilities"]["documentFormattingProvider"], true);
+        assert_eq!(
+            result["capabilities"]["documentRangeFormattingProvider"],
+            true
+        );
+        client.notify("initialized", json!({}));
+        client
+    }
+
+    /// Send a request and wait for its response: the result, or the error.
+    fn request(&mut self, method: &str, params: Value) -> Result<Value, Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        write_message(&mut self.stdin, &message).unwrap();
+        loop {
+            let message = read_message(&mut self.stdout)
+                .unwrap()
+                .expect("server closed stdout");
+            if message["id"] == id {
+                return match message.get("error") {
+                    Some(error) => Err(error.clone()),
+                    None => Ok(message["result"].clone()),
+                };
+            }
+            self.notifications.push(message);
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        let message = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        write_message(&mut self.stdin, &message).unwrap();
+    }
+
+    fn open(&mut self, uri: &str, text: &str) {
+        self.notify(
+            "textDocument/didOpen",
+            json!({ "textDocument": { "uri": uri, "languageId": "supercollider", "version": 1, "text": text } }),
+        );
+    }
+
+    fn format(&mut self, uri: &str) -> Result<Value, Value> {
+        self.request(
+            "textDocument/formatting",
+            json!({ "textDocument": { "uri": uri }, "options": { "tabSize": 4, "insertSpaces": true } }),
+        )
+    }
+
+    /// Shut down and exit cleanly, returning whether the server exited 0.
+    fn shutdown(mut self) -> bool {
+        assert_eq!(self.request("shutdown", Value::Null), Ok(Value::Null));
+        self.notify("exit", Value::Null);
+        self.child.wait().unwrap().success()
+    }
+}
+
+fn uri(path: &Path) -> String {
+    format!("file://{}", path.display())
+}
+
+/// A workspace folder whose config runs only `rules`.
+fn workspace(name: &str, rules: &[&str]) -> std::path::PathBuf {
+    let root = scratch(name);
+    let only = rules
+        .iter()
+        .map(|r| format!("\"{r}\""))
+        .collect::<Vec<_>>()
+        .join(", ");
+    fs::write(
+        root.join("sclang-format.toml"),
+        format!("phase = \"inline\"\n[rules]\nonly = [{only}]\n"),
+    )
+    .unwrap();
+    root
+}
+
+/// Apply LSP text edits to ASCII `text`.
+fn apply(text: &str, edits: &Value) -> String {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let offset = |pos: &Value| {
+        line_starts[usize::try_from(pos["line"].as_u64().unwrap()).unwrap()]
+            + usize::try_from(pos["character"].as_u64().unwrap()).unwrap()
+    };
+    let mut out = text.to_string();
+    let mut edits: Vec<&Value> = edits.as_array().unwrap().iter().collect();
+    edits.sort_by_key(|e| offset(&e["range"]["start"]));
+    for edit in edits.into_iter().rev() {
+        let (start, end) = (
+            offset(&edit["range"]["start"]),
+            offset(&edit["range"]["end"]),
+        );
+        out.replace_range(start..end, edit["newText"].as_str().unwrap());
+    }
+    out
+}
+
+#[test]
+fn formatting_returns_minimal_edits_against_the_open_text() {
+    let root = workspace("lsp_formatting", &["spaces_around_assignment"]);
+    let doc = uri(&root.join("a.scd"));
+    let text = "a=1;\nb = 2;\nc=3;\n";
+
+    let mut client = Client::initialized(&[&root]);
+    client.open(&doc, text);
+    let edits = client.format(&doc).unwrap();
+    assert_eq!(apply(text, &edits), "a = 1;\nb = 2;\nc = 3;\n");
+    // Only the `=`s are touched; the untouched line gets no edit at all.
+    assert_eq!(
+        edits[0],
+        json!({
+            "range": { "start": { "line": 0, "character": 1 }, "end": { "line": 0, "character": 2 } },
+            "newText": " = ",
+        })
+    );
+    assert_eq!(edits.as_array().unwrap().len(), 2);
+
+    // Edits follow the text as changed in the editor, not what is on disk.
+    client.notify(
+        "textDocument/didChange",
+        json!({ "textDocument": { "uri": doc, "version": 2 }, "contentChanges": [{ "text": "a = 1;\n" }] }),
+    );
+    assert_eq!(client.format(&doc), Ok(json!([])));
+    assert!(client.shutdown());
+}
+
+#[test]
+fn range_formatting_only_touches_the_range() {
+    let root = workspace("lsp_range", &["spaces_around_assignment"]);
+    let doc = uri(&root.join("a.scd"));
+    let text = "a=1;\nb=2;\nc=3;\n";
+
+    let mut client = Client::initialized(&[&root]);
+    client.open(&doc, text);
+    let edits = client
+        .request(
+            "textDocument/rangeFormatting",
+            json!({
+                "textDocument": { "uri": doc },
+                "range": { "start": { "line": 1, "character": 0 }, "end": { "line": 1, "character": 4 } },
+                "options": { "tabSize": 4, "insertSpaces": true },
+            }),
+        )
+        .unwrap();
+    assert_eq!(apply(text, &edits), "a=1;\nb = 2;\nc=3;\n");
+    assert!(client.shutdown());
+}
+
+#[test]
+fn each_workspace_folder_uses_its_own_config() {
+    let spacing = workspace("lsp_ws_assignment", &["spaces_around_assignment"]);
+    let commas = workspace("lsp_ws_commas", &["spaces_after_commas"]);
+    let text = "a=f(1,2);\n";
+
+    let mut client = Client::initialized(&[&spacing, &commas]);
+    for (root, expected) in [(&spacing, "a = f(1,2);\n"), (&commas, "a=f(1, 2);\n")] {
+        let doc = uri(&root.join("nested").join("a.scd"));
+        client.open(&doc, text);
+        let edits = client.format(&doc).unwrap();
+        assert_eq!(apply(text, &edits), expected, "{}", root.display());
+    }
+
+    // A document of another scheme goes by the folder its path is in.
+    let cell = format!(
+        "vscode-notebook-cell:{}#W0sZmlsZQ",
+        commas.join("book.ipynb").display()
+    );
+    client.open(&cell, text);
+    let edits = client.format(&cell).unwrap();
+    assert_eq!(apply(text, &edits), "a=f(1, 2);\n");
+
+    // A buffer never saved falls back to the first workspace folder.
+    client.open("untitled:Untitled-1", text);
+    let edits = client.format("untitled:Untitled-1").unwrap();
+    assert_eq!(apply(text, &edits), "a = f(1,2);\n");
+    assert!(client.shutdown());
+}
+
+#[test]
+fn a_zero_tab_size_is_rejected_when_it_would_set_the_indent() {
+    let mut client = Client::initialized(&[]);
+    client.open("untitled:Untitled-1", "a = 1;\n");
+    let err = client
+        .request(
+            "textDocument/formatting",
+            json!({
+                "textDocument": { "uri": "untitled:Untitled-1" },
+                "options": { "tabSize": 0, "insertSpaces": true },
+            }),
+        )
+        .unwrap_err();
+    assert_eq!(err["code"], -32602);
+    assert_eq!(err["message"], "options.tabSize must be at least 1, not 0");
+    assert!(client.shutdown());
+}
+
+#[test]
+fn failures_are_error_responses_and_the_server_keeps_going() {
+    let root = workspace("lsp_errors", &["spaces_around_assignment"]);
+    let doc = uri(&root.join("a.scd"));
+
+    let mut client = Client::start();
+    let error = client.format(&doc).unwrap_err();
+    assert_eq!(error["code"], -32002, "{error}");
+
+    let mut client = Client::initialized(&[&root]);
+    // Half-typed code is not an error, there is just nothing to change yet.
+    client.open(&doc, "x = (;\n");
+    assert_eq!(client.format(&doc), Ok(Value::Null));
+
+    // A body that is not JSON gets a parse error, and the server goes on.
+    let body = "{ not json";
+    write!(client.stdin, "Content-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+    let error = read_message(&mut client.stdout).unwrap().unwrap();
+    assert_eq!(error["id"], Value::Null);
+    assert_eq!(error["error"]["code"], -32700, "{error}");
+
+    let error = client.request("textDocument/hover", json!({})).unwrap_err();
+    assert_eq!(error["code"], -32601, "{error}");
+
+    let error = client.format("file:///not/open.scd").unwrap_err();
+    assert_eq!(error["code"], -32602, "{error}");
+
+    // Directive warnings go to the editor's log.
+    let text = "x=1;\n// sclang-format-disable-next-line no_such_rule\ny=2;\n";
+    client.open(&doc, text);
+    let edits = client.format(&doc).unwrap();
+    assert_eq!(
+        apply(text, &edits),
+        "x = 1;\n// sclang-format-disable-next-line no_such_rule\ny = 2;\n"
+    );
+    let [log] = client.notifications.as_slice() else {
+        panic!("expected one notification: {:?}", client.notifications);
+    };
+    assert_eq!(log["method"], "window/logMessage");
+    assert!(
+        log["params"]["message"]
+            .as_str()
+            .unwrap()
+            .ends_with("line 2: unknown rule `no_such_rule` in `sclang-format-disable-next-line`"),
+        "{log}"
+    );
+    assert!(client.shutdown());
+}
+
+#[test]
+fn exiting_without_shutdown_exits_with_one() {
+    let mut client = Client::initialized(&[]);
+    client.notify("exit", Value::Null);
+    assert_eq!(client.child.wait().unwrap().code(), Some(1));
+}
@@ -0,0 +1,90 @@
+use glob::glob;
+use sclang_format::{FormatOptions, Phase, format_source_with_options};
+mod common;
+use common::{ctx, edit};
+
+/// Run one phase on its own over every `tests/phase_fixtures/<phase>/*/input.scd`.
+fn snapshot_phase(phase: Phase) {
+    let pattern = format!("tests/phase_fixtures/{phase}/*/input.scd");
+    for entry in glob(&pattern).unwrap() {
+        let input_path = entry.unwrap();
+        let input = std::fs::read_to_string(&input_path).unwrap();
+        let opts = FormatOptions {
+            phase,
+            allow_errors: true,
+            ..FormatOptions::default()
+        };
+        let out = format_source_with_options(&input, &opts).unwrap();
+        // insta normalizes line endings; spell out the carriage returns the
+        // output keeps so the snapshot shows them.
+        let out = out.replace('\r', "\\r");
+
+        let rel = input_path.strip_prefix("tests/phase_fixtures").unwrap();
+        let name = rel.parent().unwrap().to_string_lossy().replace('/', "__");
+        insta::with_settings!({
+            snapshot_suffix => "out",
+            input_file => rel,
+        }, {
+            insta::assert_snapshot!(name, out);
+        });
+    }
+}
+
+#[test]
+fn pre_phase_fixtures() {
+    snapshot_phase(Phase::Pre);
+}
+
+#[test]
+fn post_phase_fixtures() {
+    snapshot_phase(Phase::Post);
+}
+
+#[test]
+fn line_endings_leave_carriage_returns_in_literals_alone() {
+    let opts = FormatOptions {
+        phase: Phase::Pre,
+        allow_errors: true,
+        ..FormatOptions::default()
+    };
+    assert_eq!(
+        format_source_with_options("x = \"a\rb\";\r\n// note\r\ny = 'c\rd';\r", &opts).unwrap(),
+        "x = \"a\rb\";\n// note\ny = 'c\rd';\n"
+    );
+}
+
+#[test]
+fn line_endings_fixture_keeps_the_carriage_return_in_its_string() {
+    // Byte for byte, unlike the snapshot.
+    let input = std::fs::read_to_string("tests/phase_fixtures/pre/line_endings/input.scd").unwrap();
+    let opts = FormatOptions {
+        phase: Phase::Pre,
+        allow_errors: true,
+        ..FormatOptions::default()
+    };
+    let out = format_source_with_options(&input, &opts).unwrap();
+    assert!(out.contains("\"a\r\nb\""), "{out:?}");
+    assert_eq!(out.matches('\r').count(), 1, "{out:?}");
+}
+
+#[test]
+fn post_phase_ends_the_file_with_exactly_one_newline() {
+    let post = |src: &str| sclang_format::format_source(src, "post").unwrap();
+    assert_eq!(post("x = 1;"), "x = 1;\n");
+    assert_eq!(post("x = 1;\n\n\n"), "x = 1;\n");
+    assert_eq!(post(""), "");
+}
+
+#[test]
+fn post_phase_rejects_output_that_no_longer_parses() {
+    use sclang_format::{Error, rules};
+
+    let src = "x = (1 + 2);\n";
+    let mut cx = ctx(src);
+    rules::run_post(&mut cx).unwrap();
+
+    let close = src.find(')').unwrap();
+    cx.apply_edits(vec![edit(close, close + 1, "")]).unwrap();
+    let err = rules::run_post(&mut cx).unwrap_err();
+    assert!(matches!(err, Error::BrokenOutput(_)), "{err:#}");
+}
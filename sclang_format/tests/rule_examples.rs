@@ -0,0 +1,41 @@
+use pretty_assertions::assert_eq;
+use sclang_format::rules;
+mod common;
+use common::ctx;
+
+#[test]
+fn every_rule_example_matches_its_rule() {
+    for rule in rules::all() {
+        let example = rule.example();
+        let mut cx = ctx(example.before);
+        let edits = rule.run(&mut cx).unwrap();
+        assert!(
+            edits > 0,
+            "{}: example does not trigger the rule",
+            rule.name()
+        );
+        assert_eq!(
+            cx.to_string(),
+            example.after,
+            "{}: stale example",
+            rule.name()
+        );
+    }
+}
+
+#[test]
+fn every_rule_is_described() {
+    for rule in rules::all() {
+        assert!(
+            !rule.description().is_empty(),
+            "{} has no description",
+            rule.name()
+        );
+        assert_ne!(
+            rule.example().before,
+            rule.example().after,
+            "{}",
+            rule.name()
+        );
+    }
+}
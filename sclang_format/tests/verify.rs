@@ -0,0 +1,48 @@
+use sclang_format::rules;
+use sclang_format::verify::{self, Snapshot, VerifyError};
+mod common;
+use common::{ctx, edit};
+
+#[test]
+fn rule_examples_only_change_layout_or_known_rewrites() {
+    for rule in rules::all() {
+        let mut cx = ctx(rule.example().before);
+        let before = Snapshot::of(&cx);
+        rule.run(&mut cx).unwrap();
+        if let Err(e) = verify::compare(&before, &Snapshot::of(&cx), Some(rule.name())) {
+            panic!("{e}");
+        }
+    }
+}
+
+#[test]
+fn a_changed_token_is_reported_with_its_position() {
+    let mut cx = ctx("a = 1;\nb = a;\n");
+    let before = Snapshot::of(&cx);
+    cx.apply_edits(vec![edit(11, 12, "c")]).unwrap();
+    let err = verify::compare(&before, &Snapshot::of(&cx), Some("some_rule")).unwrap_err();
+    assert_eq!(
+        err,
+        VerifyError {
+            rule: Some("some_rule"),
+            line: 2,
+            column: 5,
+            expected: Some("a".to_string()),
+            found: Some("c".to_string()),
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "rule `some_rule` changed the program at 2:5: expected `a`, found `c`"
+    );
+}
+
+#[test]
+fn comment_text_is_compared_too() {
+    let mut cx = ctx("a = 1; // one\n");
+    let before = Snapshot::of(&cx);
+    cx.apply_edits(vec![edit(10, 13, "two")]).unwrap();
+    let err = verify::compare(&before, &Snapshot::of(&cx), None).unwrap_err();
+    assert_eq!(err.line, 1);
+    assert_eq!(err.column, 8);
+}